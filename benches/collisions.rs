@@ -0,0 +1,98 @@
+//! Compares moving balls through a crowded level with the broadphase grid
+//! against testing every ball against every collider.
+//!
+//! Run it with `cargo host-bench`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::time::{Duration, Instant};
+
+use bevy::math::bounding::Aabb2d;
+use bevy::prelude::*;
+use common::{Harness, HEIGHT, WIDTH};
+use embedded_graphics::prelude::Size;
+use esp32_breakout_bevy::game::{
+    ball::{Ball, BALL_SIZE},
+    block::{Block, BrickKind},
+    broadphase::{Broadphase, CELL_SIZE},
+    collision::{Collider, Wall},
+    Position, Velocity,
+};
+
+const COLUMNS: usize = 16;
+const ROWS: usize = 15;
+const BALLS: usize = 8;
+const TICKS: u32 = 2000;
+
+fn main() {
+    let screen = Vec2::new(WIDTH as f32, HEIGHT as f32);
+    let all_pairs = time_ticks(screen);
+    let grid = time_ticks(CELL_SIZE);
+
+    println!(
+        "{} blocks, {BALLS} balls: all pairs {:.1} us/tick, grid {:.1} us/tick ({:.1}x)",
+        COLUMNS * ROWS,
+        micros_per_tick(all_pairs),
+        micros_per_tick(grid),
+        all_pairs.as_secs_f64() / grid.as_secs_f64(),
+    );
+}
+
+/// Runs the simulation tick `TICKS` times with a grid of `cell_size`. A single
+/// cell covering the whole screen puts every collider in the same bucket.
+fn time_ticks(cell_size: Vec2) -> Duration {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.despawn_blocks();
+    for (ball, _) in harness.balls() {
+        harness.world_mut().despawn(ball);
+    }
+
+    let screen = Vec2::new(WIDTH as f32, HEIGHT as f32);
+    let world = harness.world_mut();
+    world.insert_resource(Broadphase::new(
+        Aabb2d::new(screen / 2.0, screen / 2.0),
+        cell_size,
+    ));
+
+    // Close the bottom, so no ball gets lost while measuring.
+    world.spawn((
+        Wall,
+        Position(Vec2::new(-1000.0, HEIGHT as f32)),
+        Collider {
+            half_size: Vec2::new(WIDTH as f32 / 2.0 + 1000.0, 500.0),
+        },
+    ));
+    for row in 0..ROWS {
+        for column in 0..COLUMNS {
+            world.spawn((
+                Block {
+                    kind: BrickKind::Normal,
+                    lives: u8::MAX,
+                },
+                Position(Vec2::new(column as f32 * 8.0, 4.0 + row as f32 * 3.0)),
+                Collider::new(Size::new(7, 2)),
+            ));
+        }
+    }
+    for index in 0..BALLS {
+        let angle = index as f32 / BALLS as f32 * core::f32::consts::TAU;
+        world.spawn((
+            Ball,
+            Position(Vec2::new(8.0 + index as f32 * 14.0, 54.0)),
+            Velocity(Vec2::from_angle(angle) * 60.0),
+            Collider::new(BALL_SIZE),
+        ));
+    }
+
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        harness.world_mut().run_schedule(FixedUpdate);
+    }
+    start.elapsed()
+}
+
+fn micros_per_tick(elapsed: Duration) -> f64 {
+    elapsed.as_secs_f64() * 1e6 / f64::from(TICKS)
+}
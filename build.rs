@@ -1,4 +1,27 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Widest grid that fits on the screen, in bricks.
+const MAX_COLUMNS: usize = 6;
+/// Most rows that keep the bricks in the top half of the screen.
+const MAX_ROWS: usize = 5;
+/// Grid character of each `BrickKind`.
+const BRICKS: &[(char, &str)] = &[
+    ('-', "Weak"),
+    ('#', "Normal"),
+    ('=', "Strong"),
+    ('X', "Indestructible"),
+    ('*', "Explosive"),
+];
+
 fn main() {
+    generate_levels();
+
+    // The linker scripts only exist for the ESP32, host builds link normally.
+    if std::env::var("CARGO_CFG_TARGET_ARCH").as_deref() != Ok("xtensa") {
+        return;
+    }
+
     linker_be_nice();
     println!("cargo:rustc-link-arg=-Tdefmt.x");
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
@@ -51,3 +74,151 @@ fn linker_be_nice() {
         std::env::current_exe().unwrap().display()
     );
 }
+
+/// Compiles the level files under `levels/` into `$OUT_DIR/levels.rs`, failing
+/// the build on the first invalid level.
+fn generate_levels() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=levels");
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir("levels")
+        .expect("failed to read the levels directory")
+        .map(|entry| entry.expect("failed to read the levels directory").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        panic!("no levels found, add at least one `levels/*.txt` file");
+    }
+
+    let mut out = String::from("pub const LEVELS: &[Level] = &[\n");
+    for path in &paths {
+        let level = parse_level(path).unwrap_or_else(|error| panic!("{error}"));
+        out.push_str(&level);
+    }
+    out.push_str("];\n");
+
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("levels.rs"), out).expect("failed to write levels.rs");
+}
+
+/// Parses one level file into a `Level` expression.
+fn parse_level(path: &Path) -> Result<String, String> {
+    let source =
+        std::fs::read_to_string(path).map_err(|error| format!("{}: {error}", path.display()))?;
+    let lines: Vec<&str> = source.lines().collect();
+    let error =
+        |line: usize, message: String| format!("{}:{}: {message}", path.display(), line + 1);
+
+    let grid_start = lines
+        .iter()
+        .position(|line| line.trim() == "---")
+        .map_or(0, |separator| separator + 1);
+
+    let mut name = path.file_stem().unwrap().to_string_lossy().into_owned();
+    let mut ball_speed = String::from("None");
+    let mut paddle_width = String::from("None");
+    for (index, line) in lines.iter().enumerate().take(grid_start.saturating_sub(1)) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            return Err(error(
+                index,
+                format!("expected `key: value`, found `{line}`"),
+            ));
+        };
+        let value = value.trim();
+        match key.trim() {
+            "name" => name = value.to_string(),
+            "ball_speed" => match value.parse::<f32>() {
+                Ok(speed) if speed.is_finite() && speed > 0.0 => {
+                    ball_speed = format!("Some({speed:?})")
+                }
+                _ => {
+                    return Err(error(
+                        index,
+                        format!("`{value}` is not a positive ball speed"),
+                    ))
+                }
+            },
+            "paddle_width" => match value.parse::<u32>() {
+                Ok(width @ 4..=128) => paddle_width = format!("Some({width})"),
+                _ => {
+                    return Err(error(
+                        index,
+                        format!("`{value}` is not a paddle width between 4 and 128 pixels"),
+                    ))
+                }
+            },
+            other => {
+                return Err(error(
+                    index,
+                    format!(
+                        "unknown setting `{other}`, expected `name`, `ball_speed` or \
+                         `paddle_width`"
+                    ),
+                ))
+            }
+        }
+    }
+
+    // Trailing blank lines don't count as rows.
+    let grid_end = lines
+        .iter()
+        .rposition(|line| !line.trim_end().is_empty())
+        .map_or(grid_start, |last| (last + 1).max(grid_start));
+    let rows = &lines[grid_start..grid_end];
+    if rows.len() > MAX_ROWS {
+        return Err(error(
+            grid_start + MAX_ROWS,
+            format!(
+                "grid has {} rows, at most {MAX_ROWS} fit in the top half of the screen",
+                rows.len()
+            ),
+        ));
+    }
+
+    let mut blocks = String::new();
+    let mut breakable = 0;
+    for (row, line) in rows.iter().enumerate() {
+        let line = line.trim_end();
+        let columns = line.chars().count();
+        if columns > MAX_COLUMNS {
+            return Err(error(
+                grid_start + row,
+                format!("row is {columns} bricks wide, at most {MAX_COLUMNS} fit on the screen"),
+            ));
+        }
+        for (column, brick) in line.chars().enumerate() {
+            if brick == '.' || brick == ' ' {
+                continue;
+            }
+            let Some((_, kind)) = BRICKS.iter().find(|(symbol, _)| *symbol == brick) else {
+                return Err(format!(
+                    "{}:{}:{}: unknown brick `{brick}`, expected one of `{}`, `.` or ` `",
+                    path.display(),
+                    grid_start + row + 1,
+                    column + 1,
+                    BRICKS.iter().map(|(symbol, _)| *symbol).collect::<String>(),
+                ));
+            };
+            if *kind != "Indestructible" {
+                breakable += 1;
+            }
+            write!(
+                blocks,
+                "LevelBlock {{ column: {column}, row: {row}, kind: BrickKind::{kind} }}, "
+            )
+            .unwrap();
+        }
+    }
+    if breakable == 0 {
+        return Err(format!("{}: level has no blocks to break", path.display()));
+    }
+
+    Ok(format!(
+        "    Level {{ name: {name:?}, ball_speed: {ball_speed}, paddle_width: {paddle_width}, blocks: &[{blocks}] }},\n"
+    ))
+}
@@ -1,28 +1,35 @@
 #![no_std]
 #![no_main]
 
-use core::sync::atomic::Ordering;
-
+use alloc::boxed::Box;
 use bevy::app::App;
-use bevy::platform_support::{sync::atomic::AtomicU64, time::Instant as BevyInstant};
+use bevy::platform_support::time::Instant as BevyInstant;
 use bevy::DefaultPlugins;
+use core::cell::RefCell;
+use embedded_hal_bus::i2c::RefCellDevice;
 use esp32_breakout_bevy::game::resources::RandResource;
-use esp_hal::analog::adc::{Adc, AdcConfig};
+use esp_hal::clock::CpuClock;
+use esp_hal::delay::Delay;
 use esp_hal::gpio::{Input, InputConfig, Pull};
+use esp_hal::i2c::master::I2c;
 use esp_hal::main;
 use esp_hal::rng::Rng;
 use esp_hal::time::Rate;
-use esp_hal::{analog::adc::Attenuation, clock::CpuClock};
 use esp_println as _;
 
 use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
 
 use esp32_breakout_bevy as lib;
 use lib::game::{
-    resources::{AdcResource, DisplayResolution, DisplayResource, JoyStickResource},
-    start_game,
+    hal::{Button, RandomSource},
+    resources::{DisplayResolution, DisplayResource},
+    runner::frame_limited,
+    BreakoutPlugin, BreakoutRenderPlugin,
 };
 
+#[cfg(all(feature = "encoder", feature = "tilt"))]
+compile_error!("the `encoder` and `tilt` features pick different controllers, enable only one");
+
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
     esp_println::println!("Panic occurred: {:?}", info);
@@ -43,16 +50,20 @@ fn main() -> ! {
     // esp_alloc::psram_allocator!(peripherals.PSRAM, esp_hal::psram);
     esp_alloc::heap_allocator!(#[link_section = ".dram2_uninit"] size: 94000);
 
-    // Initialize the OLED Display
-    let i2c_bus = esp_hal::i2c::master::I2c::new(
+    // The display and the tilt sensor share the I2C bus, each through its own
+    // device handle. The bus lives as long as the firmware does.
+    let i2c = I2c::new(
         peripherals.I2C0,
         esp_hal::i2c::master::Config::default().with_frequency(Rate::from_khz(400)),
     )
     .expect("failed to initialize I2C")
     .with_scl(peripherals.GPIO18)
     .with_sda(peripherals.GPIO23);
+    let i2c_bus: &'static RefCell<I2c<'static, esp_hal::Blocking>> =
+        Box::leak(Box::new(RefCell::new(i2c)));
 
-    let interface = I2CDisplayInterface::new(i2c_bus);
+    // Initialize the OLED Display
+    let interface = I2CDisplayInterface::new(RefCellDevice::new(i2c_bus));
 
     // initialize the display
     let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
@@ -62,37 +73,276 @@ fn main() -> ! {
 
     unsafe { BevyInstant::set_elapsed(elapsed_time) };
 
-    let mut adc2_config = AdcConfig::new();
-
-    let vrx_pin = adc2_config.enable_pin(peripherals.GPIO13, Attenuation::_11dB);
-    let vry_pin = adc2_config.enable_pin(peripherals.GPIO14, Attenuation::_11dB);
     let input_btn = Input::new(
         peripherals.GPIO32,
         InputConfig::default().with_pull(Pull::Up),
     );
 
-    let adc = Adc::new(peripherals.ADC2, adc2_config);
-
     let mut app = App::new();
-    app.add_plugins(DefaultPlugins)
-        .insert_non_send_resource(DisplayResource { display })
-        .insert_non_send_resource(DisplayResolution {
-            width: u32::from(display_width),
-            height: u32::from(display_height),
-        })
-        .insert_non_send_resource(JoyStickResource {
-            vrx_pin,
-            vry_pin,
-            btn: input_btn,
-        })
-        .insert_non_send_resource(AdcResource { adc })
-        .insert_non_send_resource(RandResource {
-            rng: Rng::new(peripherals.RNG),
-        });
-    start_game(app)
+    app.add_plugins((
+        DefaultPlugins,
+        BreakoutPlugin,
+        BreakoutRenderPlugin::<DisplayType>::default(),
+    ))
+    .set_runner(frame_limited(Delay::new()))
+    .insert_non_send_resource(DisplayResource { display })
+    .insert_non_send_resource(DisplayResolution {
+        width: u32::from(display_width),
+        height: u32::from(display_height),
+    })
+    .insert_non_send_resource(RandResource {
+        rng: Box::new(HardwareRng(Rng::new(peripherals.RNG))),
+    });
+
+    #[cfg(not(any(feature = "encoder", feature = "tilt")))]
+    joystick::add_input(
+        &mut app,
+        peripherals.ADC2,
+        peripherals.GPIO13,
+        peripherals.GPIO14,
+        input_btn,
+    );
+    #[cfg(feature = "encoder")]
+    encoder::add_input(
+        &mut app,
+        peripherals.PCNT,
+        peripherals.GPIO25,
+        peripherals.GPIO26,
+        input_btn,
+    );
+    #[cfg(feature = "tilt")]
+    tilt::add_input(&mut app, i2c_bus, input_btn);
+
+    app.run();
+
+    // The frame limited runner only returns on `AppExit`, which nothing sends
+    // on the board.
+    loop {}
+}
+
+type DisplayType = Ssd1306<
+    I2CInterface<RefCellDevice<'static, I2c<'static, esp_hal::Blocking>>>,
+    DisplaySize128x64,
+    BufferedGraphicsMode<DisplaySize128x64>,
+>;
+
+/// Analog joystick on ADC2, with the calibration kept in flash.
+#[cfg(not(any(feature = "encoder", feature = "tilt")))]
+mod joystick {
+    use alloc::boxed::Box;
+    use alloc::rc::Rc;
+    use bevy::app::App;
+    use core::cell::RefCell;
+    use embedded_storage::{ReadStorage, Storage};
+    use esp_hal::analog::adc::{Adc, AdcConfig, AdcPin, Attenuation};
+    use esp_hal::gpio::{GpioPin, Input};
+    use esp_hal::peripherals::ADC2;
+    use esp_storage::FlashStorage;
+
+    use esp32_breakout_bevy::game::{
+        hal::{PaddleAxis, SettingsStore},
+        resources::{JoyStickResource, SettingsStoreResource},
+        BreakoutInputPlugin,
+    };
+
+    use super::JoystickButton;
+
+    pub fn add_input(
+        app: &mut App,
+        adc2: ADC2,
+        vrx: GpioPin<13>,
+        vry: GpioPin<14>,
+        btn: Input<'static>,
+    ) {
+        let mut adc2_config = AdcConfig::new();
+        let vrx_pin = adc2_config.enable_pin(vrx, Attenuation::_11dB);
+        let vry_pin = adc2_config.enable_pin(vry, Attenuation::_11dB);
+        // Both axes are sampled through the same ADC.
+        let adc = Rc::new(RefCell::new(Adc::new(adc2, adc2_config)));
+
+        app.add_plugins(BreakoutInputPlugin)
+            .insert_non_send_resource(JoyStickResource {
+                axis: Box::new(JoystickAxis {
+                    adc: adc.clone(),
+                    pin: vry_pin,
+                }),
+                cross_axis: Some(Box::new(JoystickAxis { adc, pin: vrx_pin })),
+                btn: Box::new(JoystickButton(btn)),
+            })
+            .insert_non_send_resource(SettingsStoreResource {
+                store: Box::new(FlashSettings(FlashStorage::new())),
+            });
+    }
+
+    /// Axis of the joystick on `GPIO`, sampled through ADC2.
+    struct JoystickAxis<const GPIO: u8> {
+        adc: Rc<RefCell<Adc<'static, ADC2, esp_hal::Blocking>>>,
+        pin: AdcPin<GpioPin<GPIO>, ADC2>,
+    }
+
+    impl<const GPIO: u8> PaddleAxis for JoystickAxis<GPIO> {
+        fn read(&mut self) -> Option<u16> {
+            let mut adc = self.adc.borrow_mut();
+            nb::block!(adc.read_oneshot(&mut self.pin)).ok()
+        }
+    }
+
+    /// Start of the `nvs` partition in the default partition table, which
+    /// nothing else uses on this board.
+    const SETTINGS_OFFSET: u32 = 0x9000;
+
+    /// Settings kept at [`SETTINGS_OFFSET`] in the SPI flash.
+    struct FlashSettings(FlashStorage);
+
+    impl SettingsStore for FlashSettings {
+        fn load(&mut self, bytes: &mut [u8]) -> bool {
+            self.0.read(SETTINGS_OFFSET, bytes).is_ok()
+        }
+
+        fn save(&mut self, bytes: &[u8]) -> bool {
+            self.0.write(SETTINGS_OFFSET, bytes).is_ok()
+        }
+    }
+}
+
+/// Rotary encoder on the pulse counter.
+#[cfg(feature = "encoder")]
+mod encoder {
+    use alloc::boxed::Box;
+    use bevy::app::App;
+    use esp_hal::gpio::{GpioPin, Input, InputConfig, Pull};
+    use esp_hal::pcnt::{channel, unit::Unit, Pcnt};
+    use esp_hal::peripherals::PCNT;
+
+    use esp32_breakout_bevy::game::{
+        hal::RotaryEncoder, resources::EncoderResource, EncoderInputPlugin,
+    };
+
+    use super::JoystickButton;
+
+    pub fn add_input(
+        app: &mut App,
+        pcnt: PCNT,
+        pin_a: GpioPin<25>,
+        pin_b: GpioPin<26>,
+        btn: Input<'static>,
+    ) {
+        let pcnt = Pcnt::new(pcnt);
+        let encoder = PcntEncoder::new(pcnt.unit0, pin_a, pin_b);
+
+        app.add_plugins(EncoderInputPlugin)
+            .insert_non_send_resource(EncoderResource {
+                encoder: Box::new(encoder),
+                btn: Box::new(JoystickButton(btn)),
+            });
+    }
+
+    /// Rotary encoder on pulse counter unit 0, counting every edge of both
+    /// pins.
+    struct PcntEncoder {
+        unit: Unit<'static, 0>,
+        // Keeps both pins set up as pulled up inputs.
+        _pins: [Input<'static>; 2],
+    }
+
+    impl PcntEncoder {
+        fn new(unit: Unit<'static, 0>, pin_a: GpioPin<25>, pin_b: GpioPin<26>) -> Self {
+            let config = InputConfig::default().with_pull(Pull::Up);
+            let pin_a = Input::new(pin_a, config);
+            let pin_b = Input::new(pin_b, config);
+
+            // Ignore glitches shorter than 10 us of the 80 MHz APB clock.
+            unit.set_filter(Some(10 * 80))
+                .expect("failed to set encoder filter");
+            unit.clear();
+
+            // Each channel counts the edges of one pin, in the direction given
+            // by the level of the other.
+            let ch0 = &unit.channel0;
+            ch0.set_ctrl_signal(pin_a.peripheral_input());
+            ch0.set_edge_signal(pin_b.peripheral_input());
+            ch0.set_ctrl_mode(channel::CtrlMode::Reverse, channel::CtrlMode::Keep);
+            ch0.set_input_mode(channel::EdgeMode::Increment, channel::EdgeMode::Decrement);
+
+            let ch1 = &unit.channel1;
+            ch1.set_ctrl_signal(pin_b.peripheral_input());
+            ch1.set_edge_signal(pin_a.peripheral_input());
+            ch1.set_ctrl_mode(channel::CtrlMode::Reverse, channel::CtrlMode::Keep);
+            ch1.set_input_mode(channel::EdgeMode::Decrement, channel::EdgeMode::Increment);
+
+            unit.resume();
+            Self {
+                unit,
+                _pins: [pin_a, pin_b],
+            }
+        }
+    }
+
+    impl RotaryEncoder for PcntEncoder {
+        fn take_counts(&mut self) -> i32 {
+            // An edge between reading and clearing is lost, a detent is four
+            // counts so that goes unnoticed.
+            let counts = self.unit.value();
+            self.unit.clear();
+            i32::from(counts)
+        }
+    }
+}
+
+/// MPU6050 accelerometer sharing the I2C bus with the display.
+#[cfg(feature = "tilt")]
+mod tilt {
+    use alloc::boxed::Box;
+    use bevy::app::App;
+    use core::cell::RefCell;
+    use embedded_hal_bus::i2c::RefCellDevice;
+    use esp_hal::gpio::Input;
+    use esp_hal::i2c::master::I2c;
+
+    use esp32_breakout_bevy::game::{
+        resources::TiltResource,
+        tilt::{Mpu6050, MPU6050_ADDRESS},
+        TiltInputPlugin,
+    };
+
+    use super::JoystickButton;
+
+    pub fn add_input(
+        app: &mut App,
+        i2c_bus: &'static RefCell<I2c<'static, esp_hal::Blocking>>,
+        btn: Input<'static>,
+    ) {
+        let mut sensor = Mpu6050::new(RefCellDevice::new(i2c_bus), MPU6050_ADDRESS);
+        sensor.init().expect("failed to init MPU6050");
+
+        app.add_plugins(TiltInputPlugin)
+            .insert_non_send_resource(TiltResource {
+                sensor: Box::new(sensor),
+                btn: Box::new(JoystickButton(btn)),
+            });
+    }
+}
+
+/// Button of the joystick, encoder or tilt controller, pulled up and active
+/// low.
+struct JoystickButton(Input<'static>);
+
+impl Button for JoystickButton {
+    fn is_pressed(&mut self) -> bool {
+        self.0.is_low()
+    }
+}
+
+struct HardwareRng(Rng);
+
+impl RandomSource for HardwareRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.random()
+    }
 }
 
-static ELAPSED: AtomicU64 = AtomicU64::new(0);
+/// Time since boot from the esp-hal system timer, which drives Bevy's `Time`.
 fn elapsed_time() -> core::time::Duration {
-    core::time::Duration::from_nanos(ELAPSED.load(Ordering::Relaxed))
+    let since_boot = esp_hal::time::Instant::now().duration_since_epoch();
+    core::time::Duration::from_micros(since_boot.as_micros())
 }
@@ -0,0 +1,300 @@
+//! Runs the game on the host and draws the 128x64 display in the terminal.
+//!
+//! Every character cell shows two vertical pixels using Unicode half blocks.
+//! Left/Right arrows steer the paddle, Space is the joystick button and
+//! `q`/Esc quits.
+
+use core::convert::Infallible;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bevy::app::App;
+use bevy::DefaultPlugins;
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue, terminal,
+};
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use embedded_hal::delay::DelayNs;
+
+use esp32_breakout_bevy::game::{
+    hal::{Button, GameDisplay, PaddleAxis, RandomSource},
+    resources::{DisplayResolution, DisplayResource, JoyStickResource, RandResource},
+    runner::frame_limited,
+    BreakoutInputPlugin, BreakoutPlugin, BreakoutRenderPlugin,
+};
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+
+// Raw ADC values the joystick reports for each stick position.
+const AXIS_LEFT: u16 = 4095;
+const AXIS_CENTER: u16 = 2048;
+const AXIS_RIGHT: u16 = 0;
+
+/// Without key release events a key counts as held for this long after the
+/// last (auto-repeated) press.
+const HOLD_TIMEOUT: Duration = Duration::from_millis(150);
+
+fn main() {
+    let keyboard = Rc::new(RefCell::new(Keyboard::new()));
+    enter_terminal(keyboard.borrow().release_events).expect("failed to set up terminal");
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        leave_terminal();
+        default_hook(info);
+    }));
+
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins,
+        BreakoutPlugin,
+        BreakoutInputPlugin,
+        BreakoutRenderPlugin::<TerminalDisplay>::default(),
+    ))
+    .set_runner(frame_limited(StdDelay))
+    .insert_non_send_resource(DisplayResource {
+        display: TerminalDisplay::new(),
+    })
+    .insert_non_send_resource(DisplayResolution {
+        width: WIDTH as u32,
+        height: HEIGHT as u32,
+    })
+    .insert_non_send_resource(JoyStickResource {
+        axis: Box::new(KeyboardAxis(keyboard.clone())),
+        cross_axis: None,
+        btn: Box::new(KeyboardButton(keyboard)),
+    })
+    .insert_non_send_resource(RandResource {
+        rng: Box::new(XorShiftRng::from_time()),
+    });
+    app.run();
+}
+
+fn enter_terminal(release_events: bool) -> io::Result<()> {
+    terminal::enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    if release_events {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+    Ok(())
+}
+
+fn leave_terminal() {
+    let mut stdout = io::stdout();
+    let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+    let _ = execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Frame buffer that is printed to the terminal on flush.
+struct TerminalDisplay {
+    pixels: [bool; WIDTH * HEIGHT],
+}
+
+impl TerminalDisplay {
+    fn new() -> Self {
+        Self {
+            pixels: [false; WIDTH * HEIGHT],
+        }
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * WIDTH + x]
+    }
+}
+
+impl OriginDimensions for TerminalDisplay {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for TerminalDisplay {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if x < WIDTH && y < HEIGHT {
+                self.pixels[y * WIDTH + x] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GameDisplay for TerminalDisplay {
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let mut frame = String::with_capacity((WIDTH * 3 + 2) * HEIGHT / 2);
+        for y in (0..HEIGHT).step_by(2) {
+            for x in 0..WIDTH {
+                frame.push(match (self.pixel(x, y), self.pixel(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            frame.push_str("\r\n");
+        }
+
+        let mut stdout = io::stdout().lock();
+        // A broken terminal is not worth crashing the simulator over.
+        let _ = queue!(stdout, cursor::MoveTo(0, 0));
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Key {
+    down: bool,
+    last_press: Option<Instant>,
+}
+
+impl Key {
+    fn is_held(&self, release_events: bool) -> bool {
+        if release_events {
+            self.down
+        } else {
+            self.last_press
+                .is_some_and(|pressed| pressed.elapsed() < HOLD_TIMEOUT)
+        }
+    }
+}
+
+/// Key state shared by the simulated joystick axis and button.
+struct Keyboard {
+    left: Key,
+    right: Key,
+    fire: Key,
+    /// Whether the terminal reports key releases, otherwise held keys are
+    /// detected from auto-repeat.
+    release_events: bool,
+}
+
+impl Keyboard {
+    fn new() -> Self {
+        Self {
+            left: Key::default(),
+            right: Key::default(),
+            fire: Key::default(),
+            release_events: terminal::supports_keyboard_enhancement().unwrap_or(false),
+        }
+    }
+
+    fn poll(&mut self) {
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            let Ok(Event::Key(key)) = event::read() else {
+                continue;
+            };
+            self.handle(key);
+        }
+    }
+
+    fn handle(&mut self, event: KeyEvent) {
+        let quit = matches!(event.code, KeyCode::Esc | KeyCode::Char('q'))
+            || (event.code == KeyCode::Char('c')
+                && event.modifiers.contains(KeyModifiers::CONTROL));
+        if quit {
+            leave_terminal();
+            std::process::exit(0);
+        }
+
+        let key = match event.code {
+            KeyCode::Left => &mut self.left,
+            KeyCode::Right => &mut self.right,
+            KeyCode::Char(' ') => &mut self.fire,
+            _ => return,
+        };
+        match event.kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                key.down = true;
+                key.last_press = Some(Instant::now());
+            }
+            KeyEventKind::Release => key.down = false,
+        }
+    }
+}
+
+struct KeyboardAxis(Rc<RefCell<Keyboard>>);
+
+impl PaddleAxis for KeyboardAxis {
+    fn read(&mut self) -> Option<u16> {
+        let mut keyboard = self.0.borrow_mut();
+        keyboard.poll();
+
+        let release_events = keyboard.release_events;
+        let value = match (
+            keyboard.left.is_held(release_events),
+            keyboard.right.is_held(release_events),
+        ) {
+            (true, false) => AXIS_LEFT,
+            (false, true) => AXIS_RIGHT,
+            _ => AXIS_CENTER,
+        };
+        Some(value)
+    }
+}
+
+struct KeyboardButton(Rc<RefCell<Keyboard>>);
+
+impl Button for KeyboardButton {
+    fn is_pressed(&mut self) -> bool {
+        let mut keyboard = self.0.borrow_mut();
+        keyboard.poll();
+
+        let release_events = keyboard.release_events;
+        keyboard.fire.is_held(release_events)
+    }
+}
+
+struct XorShiftRng(u32);
+
+impl XorShiftRng {
+    fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_nanos());
+        // xorshift gets stuck on a zero state
+        Self(nanos | 1)
+    }
+}
+
+impl RandomSource for XorShiftRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+}
+
+struct StdDelay;
+
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        std::thread::sleep(Duration::from_nanos(ns.into()));
+    }
+}
@@ -0,0 +1,34 @@
+//! What the player wants to do, whatever the controller.
+//!
+//! Input backends, like the joystick in [`BreakoutInputPlugin`] or the command
+//! stream in [`SerialInputPlugin`], fill [`InputActions`] in `PreUpdate` and the
+//! game systems only ever read that. A build or a test can swap the controller
+//! without touching the gameplay code.
+//!
+//! [`BreakoutInputPlugin`]: super::BreakoutInputPlugin
+//! [`SerialInputPlugin`]: super::SerialInputPlugin
+
+use bevy::prelude::*;
+
+/// Actions asked for this frame, cleared before the backends run.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq)]
+pub struct InputActions {
+    /// Paddle speed as a share of [`PLAYER_SPEED`], negative to the left. A
+    /// backend may go past 1 for a faster paddle.
+    ///
+    /// [`PLAYER_SPEED`]: super::player::PLAYER_SPEED
+    pub move_axis: f32,
+    /// Pixels to move the paddle by at once, for controllers that set its
+    /// position like a rotary encoder. Taken by the first fixed tick of the
+    /// frame.
+    pub move_by: f32,
+    /// Starts a round, serves a ball or picks the menu entry.
+    pub confirm: bool,
+    /// Leaves the menu.
+    pub back: bool,
+    pub pause: bool,
+}
+
+pub fn clear_actions(mut actions: ResMut<InputActions>) {
+    *actions = InputActions::default();
+}
@@ -1,178 +1,295 @@
+use core::f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4, PI};
+
+use bevy::math::bounding::{Aabb2d, BoundingVolume};
 use bevy::prelude::*;
-use embedded_graphics::{
-    prelude::{Point, Size},
-    primitives::Rectangle,
-};
+use embedded_graphics::prelude::Size;
 
 use super::{
-    block::{Block, BLOCK_SIZE},
-    player::{Player, PLAYER_SIZE},
+    collision::{Bouncy, Collider, Collision},
+    level::LEVELS,
+    player::{Player, PLAYER_SPEED},
+    powerup::{ActivePowerUps, Capsule, Caught, PowerUp, SLOW_BALL_FACTOR},
     resources::{DisplayResolution, GameStatus, RandResource},
-    state::ResetGameEvent,
     Position, Velocity,
 };
 
 pub const BALL_SIZE: Size = Size::new(4, 4);
-pub const BALL_SPEED: i32 = 1;
+/// Ball speed in pixels per second, unless the level sets its own.
+pub const BALL_SPEED: f32 = 30.0;
+/// Largest angle from vertical a new ball can be aimed at.
+pub const MAX_LAUNCH_ANGLE: f32 = FRAC_PI_4;
+/// How fast the stick turns the aim of a ball being served, in radians per second.
+pub const AIM_SPEED: f32 = FRAC_PI_2;
+/// A ball that wasn't served by then launches on its own.
+pub const SERVE_TIMEOUT_SECS: f32 = 3.0;
+/// Angle from vertical when the ball bounces off the very edge of the paddle.
+pub const MAX_BOUNCE_ANGLE: f32 = FRAC_PI_3;
+/// Extra angle given to the ball when the paddle moves at full speed as it hits.
+pub const MAX_ENGLISH_ANGLE: f32 = PI / 12.0;
+/// Pause between losing the last ball and serving a new one.
+pub const RESPAWN_SECS: f32 = 1.0;
 
 #[derive(Component)]
-#[require(Velocity)]
+#[require(Bouncy)]
 pub struct Ball;
 
+/// Ball resting on the paddle until the button launches it.
+#[derive(Component)]
+pub struct Serving {
+    /// Launch direction as angle from straight up, positive aims right.
+    pub angle: f32,
+    /// Launch speed in pixels per second.
+    pub speed: f32,
+    /// Set to launch the ball on the next tick.
+    pub launch: bool,
+    /// Launches the ball on its own once finished, for attract/demo modes.
+    pub timer: Timer,
+}
+
+impl Serving {
+    /// Turns the aim by `angle`, keeping it within [`MAX_LAUNCH_ANGLE`].
+    pub fn aim(&mut self, angle: f32) {
+        self.angle = (self.angle + angle).clamp(-MAX_LAUNCH_ANGLE, MAX_LAUNCH_ANGLE);
+    }
+
+    pub fn direction(&self) -> Vec2 {
+        Vec2::from_angle(self.angle - FRAC_PI_2)
+    }
+}
+
+/// Counts down to serving a new ball after the last one was lost.
+#[derive(Resource)]
+pub struct RespawnTimer(pub Timer);
+
+/// Whether balls bounce off each other, on unless turned off.
+#[derive(Resource)]
+pub struct BallCollisions {
+    pub enabled: bool,
+}
+
+impl Default for BallCollisions {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Puts a ball on the paddle when none is in play, at once at the start of a
+/// level and after [`RespawnTimer`] ran out when the last ball was lost.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn spawn_ball_if_empty(
     balls: Query<(&mut Position, &mut Velocity), With<Ball>>,
-    commands: Commands,
-    display_resolution: NonSendMut<DisplayResolution>,
+    mut commands: Commands,
+    respawn: Option<ResMut<RespawnTimer>>,
+    time: Res<Time>,
+    player: Query<(&Position, &Collider), (With<Player>, Without<Ball>)>,
     rand_res: NonSendMut<RandResource>,
+    game_status: Res<GameStatus>,
+    power_ups: Res<ActivePowerUps>,
 ) {
     if !balls.is_empty() {
         // Spawn ball only if it is empty
         return;
     }
 
-    spawn_ball(commands, display_resolution, rand_res);
-}
-
-pub fn spawn_ball_on_reset(
-    commands: Commands,
-    display_resolution: NonSendMut<DisplayResolution>,
-    rand_res: NonSendMut<RandResource>,
-    mut event_reader: EventReader<ResetGameEvent>,
-) {
-    let Some(_) = event_reader.read().next() else {
-        return;
-    };
+    if let Some(mut respawn) = respawn {
+        if !respawn.0.tick(time.delta()).finished() {
+            return;
+        }
+        commands.remove_resource::<RespawnTimer>();
+    }
 
-    spawn_ball(commands, display_resolution, rand_res);
+    spawn_ball(commands, player, rand_res, game_status, power_ups);
 }
 
+/// Spawns a ball waiting to be served from the middle of the paddle, aimed
+/// somewhere within [`MAX_LAUNCH_ANGLE`] of straight up.
+#[allow(clippy::type_complexity)]
 pub fn spawn_ball(
     mut commands: Commands,
-    display_resolution: NonSendMut<DisplayResolution>,
+    player: Query<(&Position, &Collider), (With<Player>, Without<Ball>)>,
     mut rand_res: NonSendMut<RandResource>,
+    game_status: Res<GameStatus>,
+    power_ups: Res<ActivePowerUps>,
 ) {
+    let mut speed = LEVELS[game_status.level].ball_speed.unwrap_or(BALL_SPEED);
+    if power_ups.is_active(PowerUp::SlowBall) {
+        speed *= SLOW_BALL_FACTOR;
+    }
     let rng = &mut rand_res.rng;
-    let rand_velocity_x = ((rng.random() as i32 % 21) - 10).clamp(-1, 1);
+    // Anywhere from MAX_LAUNCH_ANGLE left to MAX_LAUNCH_ANGLE right of straight up
+    let spread = (rng.next_u32() % 1001) as f32 / 1000.0 * 2.0 - 1.0;
+    let angle = spread * MAX_LAUNCH_ANGLE;
+
+    let Ok((paddle_position, paddle_collider)) = player.single() else {
+        return;
+    };
 
     commands.spawn((
         Ball,
-        Position(Point::new(
-            (display_resolution.width / 2) as i32,
-            (display_resolution.height / 2) as i32,
-        )),
-        Velocity {
-            x: rand_velocity_x,
-            y: -1,
+        Position(serve_position(paddle_position, paddle_collider)),
+        Collider::new(BALL_SIZE),
+        Serving {
+            angle,
+            speed,
+            launch: false,
+            timer: Timer::from_seconds(SERVE_TIMEOUT_SECS, TimerMode::Once),
         },
     ));
 }
 
-pub fn update_ball(
-    balls: Query<(&mut Position, &mut Velocity), With<Ball>>,
-    display_resolution: NonSendMut<DisplayResolution>,
+/// Keeps balls being served on the paddle and launches them when the button
+/// was pressed or they waited too long.
+#[allow(clippy::type_complexity)]
+pub fn serve_balls(
+    mut commands: Commands,
+    balls: Query<(Entity, &mut Position, &mut Velocity, &mut Serving), With<Ball>>,
+    player: Query<(&Position, &Collider), (With<Player>, Without<Ball>)>,
+    time: Res<Time>,
 ) {
-    for (mut position, mut velocity) in balls {
-        position.0.x += velocity.x * BALL_SPEED;
-        position.0.y += velocity.y * BALL_SPEED;
-
-        if position.0.x < 0 {
-            velocity.x = 1;
-        } else if position.0.x > display_resolution.width as i32 - BALL_SIZE.width as i32 {
-            velocity.x = -1;
-        }
+    let Ok((paddle_position, paddle_collider)) = player.single() else {
+        return;
+    };
+
+    for (entity, mut position, mut velocity, mut serving) in balls {
+        position.0 = serve_position(paddle_position, paddle_collider);
+        velocity.0 = Vec2::ZERO;
 
-        if position.0.y < 0 {
-            velocity.y = 1;
+        if serving.timer.tick(time.delta()).finished() || serving.launch {
+            velocity.0 = serving.direction() * serving.speed;
+            commands.entity(entity).remove::<Serving>();
         }
     }
 }
 
+/// Where a ball being served sits, on top of the middle of the paddle.
+fn serve_position(paddle_position: &Position, paddle_collider: &Collider) -> Vec2 {
+    paddle_position.0
+        + Vec2::new(
+            (paddle_collider.size().x - BALL_SIZE.width as f32) / 2.0,
+            -(BALL_SIZE.height as f32),
+        )
+}
+
+/// Aims balls that bounced off the top of the paddle. Side hits just reflect.
 #[allow(clippy::type_complexity)]
-pub fn collison_handle(
-    balls: Query<(&mut Position, &mut Velocity), With<Ball>>,
-    mut blocks: Query<(&mut Block, &mut Position), (With<Block>, Without<Ball>, Without<Player>)>,
-    mut player: Query<&mut Position, (With<Player>, Without<Ball>, Without<Block>)>,
-    mut game_status: ResMut<GameStatus>,
+pub fn aim_off_paddle(
+    mut collisions: EventReader<Collision>,
+    mut balls: Query<(&Position, &Collider, &mut Velocity), With<Ball>>,
+    player: Query<(Entity, &Position, &Collider, &Velocity), (With<Player>, Without<Ball>)>,
 ) {
-    let Ok(player_pos) = player.single_mut() else {
+    let Ok((paddle, paddle_position, paddle_collider, paddle_velocity)) = player.single() else {
         return;
     };
+    let paddle_aabb = paddle_collider.aabb(paddle_position);
 
-    //TODO: Use bevy only to check the collison
-    let player_rect = Rectangle::new(player_pos.0, PLAYER_SIZE);
-
-    for (mut ball_position, mut ball_velocity) in balls {
-        let mut ball_rect = Rectangle::new(ball_position.0, BALL_SIZE);
-        if resolve_collison(&mut ball_rect, &mut ball_velocity, &player_rect) {
-            ball_position.0 = ball_rect.top_left;
-        }
-
-        for (mut block, block_position) in blocks.iter_mut() {
-            let block_rect = Rectangle::new(block_position.0, BLOCK_SIZE);
-            if resolve_collison(&mut ball_rect, &mut ball_velocity, &block_rect) {
-                ball_position.0 = ball_rect.top_left;
-                block.lives = block.lives.saturating_sub(1);
-                if block.lives == 0 {
-                    game_status.score += 10;
-                }
-            }
+    for collision in collisions.read() {
+        if collision.b != paddle || collision.normal != Vec2::NEG_Y {
+            continue;
         }
+        let Ok((position, collider, mut velocity)) = balls.get_mut(collision.a) else {
+            continue;
+        };
+        velocity.0 = paddle_bounce(
+            &collider.aabb(position),
+            velocity.0,
+            &paddle_aabb,
+            paddle_velocity.0,
+        );
     }
 }
 
+/// Removes balls that fell off the bottom. Losing the last ball in play costs
+/// a life, ends the power-ups, clears the capsules still falling and starts the
+/// countdown to the next ball.
 pub fn remove_balls(
     mut commands: Commands,
     balls: Query<(Entity, &mut Position), With<Ball>>,
+    capsules: Query<Entity, With<Capsule>>,
     mut player: Query<&mut Player, With<Player>>,
+    mut power_ups: ResMut<ActivePowerUps>,
     display_resolution: NonSendMut<DisplayResolution>,
 ) {
     let mut removed_balls = 0;
     for (entity, position) in balls.iter() {
-        if position.0.y > display_resolution.height as i32 {
+        if position.0.y > display_resolution.height as f32 {
             removed_balls += 1;
             commands.entity(entity).despawn();
         }
     }
 
-    if removed_balls > 0 {
-        let Ok(mut player) = player.single_mut() else {
-            return;
-        };
-
-        player.lives = player.lives.saturating_sub(1);
+    if removed_balls == 0 || removed_balls < balls.iter().len() {
+        return;
     }
-}
 
-fn resolve_collison(a: &mut Rectangle, vel: &mut Velocity, b: &Rectangle) -> bool {
-    let intersection = a.intersection(b);
+    let Ok(mut player) = player.single_mut() else {
+        return;
+    };
 
-    if intersection.size.width == 0 || intersection.size.height == 0 {
-        return false;
+    player.lives = player.lives.saturating_sub(1);
+    power_ups.0.clear();
+    capsules
+        .iter()
+        .for_each(|capsule| commands.entity(capsule).despawn());
+    if player.lives > 0 {
+        commands.insert_resource(RespawnTimer(Timer::from_seconds(
+            RESPAWN_SECS,
+            TimerMode::Once,
+        )));
     }
+}
 
-    let a_center = a.center();
-    let b_center = b.center();
-    let to = b_center - a_center;
-    let to_signum = Point::new(to.x.signum(), to.y.signum());
+/// Bounces balls off each other like round balls of equal mass, unless turned
+/// off with [`BallCollisions`]. Balls held by the paddle are left out.
+#[allow(clippy::type_complexity)]
+pub fn collide_balls(
+    settings: Res<BallCollisions>,
+    mut balls: Query<(Entity, &Position, &mut Velocity, &Collider), (With<Ball>, Without<Caught>)>,
+    mut collisions: EventWriter<Collision>,
+) {
+    if !settings.enabled {
+        return;
+    }
 
-    if intersection.size.width > intersection.size.height {
-        a.top_left.y -= to_signum.y * intersection.size.height as i32;
+    let mut pairs = balls.iter_combinations_mut();
+    while let Some(
+        [(a, a_position, mut a_velocity, a_collider), (b, b_position, mut b_velocity, b_collider)],
+    ) = pairs.fetch_next()
+    {
+        let offset = a_collider.aabb(a_position).center() - b_collider.aabb(b_position).center();
+        let depth = a_collider.half_size.x + b_collider.half_size.x - offset.length();
+        let normal = offset.normalize_or_zero();
+        // Negative while the balls move towards each other.
+        let approach = (a_velocity.0 - b_velocity.0).dot(normal);
+        if depth <= 0.0 || normal == Vec2::ZERO || approach >= 0.0 {
+            continue;
+        }
 
-        vel.y = match to_signum.y {
-            1 => -vel.y.abs(),
-            -1 => vel.y.abs(),
-            0 => -vel.y,
-            _ => vel.y,
-        };
-    } else {
-        a.top_left.x -= to_signum.x * intersection.size.width as i32;
-        vel.x = match to_signum.x {
-            1 => -vel.x.abs(),
-            -1 => vel.x.abs(),
-            0 => -vel.x,
-            _ => vel.x,
-        };
+        // Swap the parts of the velocities along the normal.
+        a_velocity.0 -= normal * approach;
+        b_velocity.0 += normal * approach;
+        collisions.write(Collision {
+            a,
+            b,
+            normal,
+            depth,
+        });
     }
+}
+
+/// Direction the ball leaves the paddle in, keeping its speed.
+///
+/// The further from the centre of the paddle the ball lands, the flatter it
+/// bounces off. Moving the paddle while hitting adds "english" in the direction
+/// of movement.
+fn paddle_bounce(
+    ball: &Aabb2d,
+    ball_velocity: Vec2,
+    paddle: &Aabb2d,
+    paddle_velocity: Vec2,
+) -> Vec2 {
+    let offset = ((ball.center().x - paddle.center().x) / paddle.half_size().x).clamp(-1.0, 1.0);
+    let english = (paddle_velocity.x / PLAYER_SPEED).clamp(-1.0, 1.0) * MAX_ENGLISH_ANGLE;
+    let angle = (offset * MAX_BOUNCE_ANGLE + english).clamp(-MAX_BOUNCE_ANGLE, MAX_BOUNCE_ANGLE);
 
-    true
+    Vec2::from_angle(angle - FRAC_PI_2) * ball_velocity.length()
 }
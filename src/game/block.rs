@@ -1,38 +1,149 @@
+use alloc::vec::Vec;
+
+use bevy::math::bounding::BoundingVolume;
 use bevy::prelude::*;
-use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::prelude::Size;
 
-use super::{resources::DisplayResolution, state::ResetGameEvent, Position};
+use super::{
+    broadphase::Broadphase,
+    collision::{Collider, Collision},
+    level::LEVELS,
+    resources::{DisplayResolution, GameStatus},
+    Position,
+};
 
+/// Width of the level grid, `build.rs` rejects wider levels.
 const BLOCK_COLUMNS: usize = 6;
-const BLOCK_ROWS: usize = 5;
 pub const BLOCK_SIZE: Size = Size::new(20, 3);
 const BLOCK_PADDING: i32 = 1;
 
+/// Distance from a block an explosion reaches, enough for the direct and
+/// diagonal neighbours.
+const BLAST_REACH: f32 = 2.0;
+
+/// What a brick does when hit. Written in level files as one character each.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BrickKind {
+    /// `-`, breaks on the first hit.
+    Weak,
+    /// `#`, takes two hits.
+    Normal,
+    /// `=`, takes three hits.
+    Strong,
+    /// `X`, can't be destroyed and doesn't count towards clearing the level.
+    Indestructible,
+    /// `*`, breaks on the first hit and damages its neighbours, setting off
+    /// other explosive bricks in a chain.
+    Explosive,
+}
+
+impl BrickKind {
+    pub fn hit_points(self) -> u8 {
+        match self {
+            BrickKind::Weak | BrickKind::Explosive => 1,
+            BrickKind::Normal => 2,
+            BrickKind::Strong => 3,
+            BrickKind::Indestructible => u8::MAX,
+        }
+    }
+
+    /// Score for destroying the brick.
+    pub fn points(self) -> u32 {
+        match self {
+            BrickKind::Weak => 5,
+            BrickKind::Normal => 10,
+            BrickKind::Strong => 20,
+            BrickKind::Indestructible => 0,
+            BrickKind::Explosive => 15,
+        }
+    }
+
+    pub fn is_indestructible(self) -> bool {
+        self == BrickKind::Indestructible
+    }
+
+    pub fn is_explosive(self) -> bool {
+        self == BrickKind::Explosive
+    }
+}
+
 #[derive(Component)]
 pub struct Block {
+    pub kind: BrickKind,
     pub lives: u8,
 }
 
+impl Block {
+    pub fn new(kind: BrickKind) -> Self {
+        Self {
+            kind,
+            lives: kind.hit_points(),
+        }
+    }
+
+    /// Takes one life, returns whether this destroyed the block.
+    fn hit(&mut self) -> bool {
+        // Destroyed blocks stay around until `remove_blocks` runs.
+        if self.kind.is_indestructible() || self.lives == 0 {
+            return false;
+        }
+
+        self.lives -= 1;
+        self.lives == 0
+    }
+}
+
+/// Builds the block layout of the current level.
 pub fn spawn_blocks(
     mut commands: Commands,
     display_resolution: NonSendMut<DisplayResolution>,
-    mut events: EventReader<ResetGameEvent>,
+    game_status: Res<GameStatus>,
 ) {
-    let Some(_) = events.read().next() else {
-        return;
-    };
-
     let total_width =
         BLOCK_COLUMNS as i32 * (BLOCK_SIZE.width as i32 + BLOCK_PADDING) - BLOCK_PADDING;
     let start_x = (display_resolution.width as i32 - total_width) / 2;
     let start_y = 10;
 
-    for row in 0..BLOCK_ROWS {
-        for column in 0..BLOCK_COLUMNS {
-            let x = start_x + column as i32 * (BLOCK_SIZE.width as i32 + BLOCK_PADDING);
-            let y = start_y + row as i32 * (BLOCK_SIZE.height as i32 + BLOCK_PADDING);
+    for block in LEVELS[game_status.level].blocks {
+        let x = start_x + i32::from(block.column) * (BLOCK_SIZE.width as i32 + BLOCK_PADDING);
+        let y = start_y + i32::from(block.row) * (BLOCK_SIZE.height as i32 + BLOCK_PADDING);
+
+        commands.spawn((
+            Block::new(block.kind),
+            Position(Vec2::new(x as f32, y as f32)),
+            Collider::new(BLOCK_SIZE),
+        ));
+    }
+}
+
+/// Takes a life from every block a ball hit, scoring the ones destroyed.
+/// Destroyed explosive blocks damage the blocks around them, which can set off
+/// further explosions.
+pub fn damage_blocks(
+    mut collisions: EventReader<Collision>,
+    mut blocks: Query<(&mut Block, &Position, &Collider)>,
+    broadphase: Res<Broadphase>,
+    mut game_status: ResMut<GameStatus>,
+) {
+    let mut hits: Vec<Entity> = collisions.read().map(|collision| collision.b).collect();
+
+    while let Some(entity) = hits.pop() {
+        let Ok((mut block, position, collider)) = blocks.get_mut(entity) else {
+            continue;
+        };
+        if !block.hit() {
+            continue;
+        }
 
-            commands.spawn((Block { lives: 2 }, Position(Point { x, y })));
+        game_status.score += block.kind.points();
+        if block.kind.is_explosive() {
+            let blast = collider.aabb(position).grow(Vec2::splat(BLAST_REACH));
+            hits.extend(
+                broadphase
+                    .query(&blast)
+                    .into_iter()
+                    .filter(|neighbour| *neighbour != entity),
+            );
         }
     }
 }
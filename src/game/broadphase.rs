@@ -0,0 +1,143 @@
+//! Uniform grid over the static colliders, so a moving body only has to be
+//! tested against the colliders near its path instead of all of them.
+
+use alloc::vec::Vec;
+
+use bevy::math::bounding::Aabb2d;
+use bevy::prelude::*;
+
+use super::{collision::Collider, resources::DisplayResolution, Position, Velocity};
+
+/// Size of a grid cell in pixels. A 128x64 screen is split into 8x8 cells.
+pub const CELL_SIZE: Vec2 = Vec2::new(16.0, 8.0);
+
+/// Static colliders (everything without a [`Velocity`]) sorted into the cells
+/// of a grid covering the screen.
+///
+/// Colliders reaching past the screen, like the walls, are kept in the border
+/// cells, so looking up an area off screen still finds them.
+#[derive(Resource)]
+pub struct Broadphase {
+    origin: Vec2,
+    cell_size: Vec2,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<Entity>>,
+    /// Every entity in the grid, once.
+    entities: Vec<Entity>,
+}
+
+impl Broadphase {
+    /// Empty grid covering `bounds` with cells of `cell_size`.
+    pub fn new(bounds: Aabb2d, cell_size: Vec2) -> Self {
+        let cells = ((bounds.max - bounds.min) / cell_size)
+            .ceil()
+            .max(Vec2::ONE);
+        let columns = cells.x as usize;
+        let rows = cells.y as usize;
+
+        Self {
+            origin: bounds.min,
+            cell_size,
+            columns,
+            rows,
+            cells: (0..columns * rows).map(|_| Vec::new()).collect(),
+            entities: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.iter_mut().for_each(Vec::clear);
+        self.entities.clear();
+    }
+
+    /// Adds `entity` to every cell `aabb` overlaps.
+    pub fn insert(&mut self, entity: Entity, aabb: &Aabb2d) {
+        let (min, max) = self.cell_range(aabb);
+        for row in min.1..=max.1 {
+            for column in min.0..=max.0 {
+                self.cells[row * self.columns + column].push(entity);
+            }
+        }
+        self.entities.push(entity);
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(&entity)
+    }
+
+    /// Entities in all cells `aabb` overlaps, each listed once.
+    pub fn query(&self, aabb: &Aabb2d) -> Vec<Entity> {
+        let mut found = Vec::new();
+        let (min, max) = self.cell_range(aabb);
+        for row in min.1..=max.1 {
+            for column in min.0..=max.0 {
+                found.extend_from_slice(&self.cells[row * self.columns + column]);
+            }
+        }
+
+        // Colliders spanning several cells show up more than once.
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    /// Column and row of the first and last cell `aabb` overlaps, clamped to the
+    /// grid.
+    fn cell_range(&self, aabb: &Aabb2d) -> ((usize, usize), (usize, usize)) {
+        let last = Vec2::new((self.columns - 1) as f32, (self.rows - 1) as f32);
+        let cell = |point: Vec2| {
+            let cell = ((point - self.origin) / self.cell_size)
+                .floor()
+                .clamp(Vec2::ZERO, last);
+            (cell.x as usize, cell.y as usize)
+        };
+        (cell(aabb.min), cell(aabb.max))
+    }
+}
+
+/// Sets up the grid over the screen, unless one was inserted already.
+pub fn setup_broadphase(
+    mut commands: Commands,
+    broadphase: Option<Res<Broadphase>>,
+    display_resolution: NonSend<DisplayResolution>,
+) {
+    if broadphase.is_some() {
+        return;
+    }
+
+    let screen = Vec2::new(
+        display_resolution.width as f32,
+        display_resolution.height as f32,
+    );
+    commands.insert_resource(Broadphase::new(
+        Aabb2d::new(screen / 2.0, screen / 2.0),
+        CELL_SIZE,
+    ));
+}
+
+/// Re-sorts the static colliders into the grid after any was spawned, moved or
+/// despawned, e.g. when blocks are destroyed or a new level is built. Balls
+/// coming and going leave the grid alone.
+#[allow(clippy::type_complexity)]
+pub fn update_broadphase(
+    mut broadphase: ResMut<Broadphase>,
+    colliders: Query<(Entity, &Position, &Collider), Without<Velocity>>,
+    changed: Query<(), (With<Collider>, Without<Velocity>, Changed<Position>)>,
+    mut removed: RemovedComponents<Collider>,
+) {
+    // Read all removals, so they don't trigger another rebuild next time.
+    let removed = removed
+        .read()
+        .filter(|entity| broadphase.contains(*entity))
+        .count()
+        > 0;
+    if changed.is_empty() && !removed && !broadphase.is_changed() {
+        return;
+    }
+
+    broadphase.clear();
+    for (entity, position, collider) in &colliders {
+        broadphase.insert(entity, &collider.aabb(position));
+    }
+}
@@ -0,0 +1,204 @@
+//! Debounced joystick button and the gestures made with it.
+//!
+//! The switch contacts bounce for a few milliseconds when pressed or let go, so
+//! [`ButtonTracker`] takes a level change as soon as it's seen and then ignores
+//! the pin for [`ButtonTimings::debounce`]. That doesn't delay presses, and a
+//! press lasting a single frame still counts. Gestures are sent as events every
+//! frame by [`read_button`].
+
+use core::time::Duration;
+
+use bevy::prelude::*;
+
+use super::{
+    hal::Button,
+    resources::{EncoderResource, JoyStickResource, TiltResource},
+};
+
+/// The button went down.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct ButtonPressed;
+
+/// The button came back up.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct ButtonReleased;
+
+/// The button has been held down for [`ButtonTimings::long_press`], sent once
+/// per press.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct ButtonLongPress;
+
+/// Second press within [`ButtonTimings::double_press`] of the first, sent along
+/// with its [`ButtonPressed`].
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct ButtonDoublePress;
+
+/// What [`ButtonTracker::update`] saw happen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonGesture {
+    Pressed,
+    Released,
+    LongPress,
+    DoublePress,
+}
+
+/// How the button is debounced and how long gestures take.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct ButtonTimings {
+    /// Changes of the pin this soon after the last one are contact bounce.
+    pub debounce: Duration,
+    /// Holding the button this long is a long press.
+    pub long_press: Duration,
+    /// Most time from one press to the next for a double press.
+    pub double_press: Duration,
+}
+
+impl Default for ButtonTimings {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(20),
+            long_press: Duration::from_millis(800),
+            double_press: Duration::from_millis(300),
+        }
+    }
+}
+
+/// Debounced state of the button, fed with raw pin levels.
+#[derive(Resource, Default, Debug)]
+pub struct ButtonTracker {
+    pressed: bool,
+    /// When the debounced level last changed.
+    changed_at: Option<Duration>,
+    /// Start of the press being held, until it was reported as a long press.
+    held_since: Option<Duration>,
+    /// Last press that could start a double press.
+    last_press: Option<Duration>,
+}
+
+impl ButtonTracker {
+    /// Debounced level of the button.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Takes `pressed` as the level of the button without reporting a gesture,
+    /// e.g. for a button that was already held at power-up.
+    pub fn reset(&mut self, pressed: bool) {
+        *self = Self {
+            pressed,
+            ..default()
+        };
+    }
+
+    /// Feeds the raw level of the pin at `now` and returns the gestures it
+    /// completed, in order.
+    pub fn update(
+        &mut self,
+        pressed: bool,
+        now: Duration,
+        timings: &ButtonTimings,
+    ) -> heapless::Vec<ButtonGesture, 2> {
+        let mut gestures = heapless::Vec::new();
+        let settled = self
+            .changed_at
+            .is_none_or(|changed_at| now.saturating_sub(changed_at) >= timings.debounce);
+
+        if pressed != self.pressed && settled {
+            self.pressed = pressed;
+            self.changed_at = Some(now);
+            if pressed {
+                self.held_since = Some(now);
+                gestures.push(ButtonGesture::Pressed).unwrap();
+                match self.last_press.take() {
+                    Some(last) if now.saturating_sub(last) <= timings.double_press => {
+                        gestures.push(ButtonGesture::DoublePress).unwrap();
+                    }
+                    _ => self.last_press = Some(now),
+                }
+            } else {
+                self.held_since = None;
+                gestures.push(ButtonGesture::Released).unwrap();
+            }
+        } else if let Some(since) = self.held_since {
+            if self.pressed && now.saturating_sub(since) >= timings.long_press {
+                self.held_since = None;
+                gestures.push(ButtonGesture::LongPress).unwrap();
+            }
+        }
+
+        gestures
+    }
+}
+
+/// Debouncing and gesture events for the button of an input backend. Added by
+/// each backend with a button, so it may be added more than once.
+pub struct ButtonPlugin;
+
+impl Plugin for ButtonPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ButtonTracker>()
+            .init_resource::<ButtonTimings>()
+            .add_event::<ButtonPressed>()
+            .add_event::<ButtonReleased>()
+            .add_event::<ButtonLongPress>()
+            .add_event::<ButtonDoublePress>();
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+/// Resource of an input backend that has the button on it.
+pub trait ButtonSource: 'static {
+    fn button(&mut self) -> &mut dyn Button;
+}
+
+impl ButtonSource for JoyStickResource {
+    fn button(&mut self) -> &mut dyn Button {
+        &mut *self.btn
+    }
+}
+
+impl ButtonSource for EncoderResource {
+    fn button(&mut self) -> &mut dyn Button {
+        &mut *self.btn
+    }
+}
+
+impl ButtonSource for TiltResource {
+    fn button(&mut self) -> &mut dyn Button {
+        &mut *self.btn
+    }
+}
+
+/// Reads the button of `S` and sends the events for what it did.
+#[allow(clippy::too_many_arguments)]
+pub fn read_button<S: ButtonSource>(
+    mut source: NonSendMut<S>,
+    mut tracker: ResMut<ButtonTracker>,
+    timings: Res<ButtonTimings>,
+    time: Res<Time>,
+    mut pressed: EventWriter<ButtonPressed>,
+    mut released: EventWriter<ButtonReleased>,
+    mut long_press: EventWriter<ButtonLongPress>,
+    mut double_press: EventWriter<ButtonDoublePress>,
+) {
+    let level = source.button().is_pressed();
+    for gesture in tracker.update(level, time.elapsed(), &timings) {
+        match gesture {
+            ButtonGesture::Pressed => {
+                pressed.write(ButtonPressed);
+            }
+            ButtonGesture::Released => {
+                released.write(ButtonReleased);
+            }
+            ButtonGesture::LongPress => {
+                long_press.write(ButtonLongPress);
+            }
+            ButtonGesture::DoublePress => {
+                double_press.write(ButtonDoublePress);
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Joystick calibration screen.
+//!
+//! Shown on the first boot, when no [`JoystickCalibration`] is stored yet, and
+//! whenever the button is held down at power-up. The player first leaves the
+//! stick centred, then moves it to all of its ends. The result is saved to the
+//! [`SettingsStoreResource`] and read back on every later boot.
+
+use bevy::prelude::*;
+
+use super::{
+    actions::InputActions,
+    button::ButtonTracker,
+    resources::{JoyStickResource, SettingsStoreResource},
+    state::GameState,
+    stick::{AxisCalibration, JoystickCalibration},
+};
+
+/// Readings averaged for the centre of an axis.
+const CENTER_SAMPLES: u32 = 8;
+/// Least travel from the centre to each end for a calibration to be accepted.
+pub const MIN_TRAVEL: u16 = 400;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum CalibrationStep {
+    /// Waiting for the button with the stick at rest.
+    #[default]
+    Center,
+    /// Recording the ends while the stick is moved around.
+    Extremes,
+}
+
+/// Progress through the calibration screen.
+#[derive(Resource, Default)]
+pub struct CalibrationRun {
+    pub step: CalibrationStep,
+    /// The last attempt had too little travel and was thrown away.
+    pub failed: bool,
+    /// Latest readings of the paddle and the cross axis.
+    pub reading: [Option<u16>; 2],
+    /// Readings collected so far, for the paddle and the cross axis.
+    pub axes: [AxisCalibration; 2],
+}
+
+/// Loads the stored calibration and goes to the calibration screen if there
+/// is none or the button is held down.
+pub fn check_calibration(
+    mut commands: Commands,
+    store: Option<NonSendMut<SettingsStoreResource>>,
+    mut joystick: NonSendMut<JoyStickResource>,
+    mut button: ResMut<ButtonTracker>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(mut store) = store else {
+        return;
+    };
+
+    let mut bytes = [0; JoystickCalibration::BYTES];
+    let stored = store
+        .store
+        .load(&mut bytes)
+        .then(|| JoystickCalibration::from_bytes(&bytes))
+        .flatten();
+    if let Some(calibration) = stored {
+        commands.insert_resource(calibration);
+    }
+
+    // The button has to be let go before it counts as pressed on the screen.
+    let held = joystick.btn.is_pressed();
+    button.reset(held);
+    if stored.is_none() || held {
+        next_state.set(GameState::Calibrating);
+    }
+}
+
+pub fn start_calibration(mut commands: Commands) {
+    commands.insert_resource(CalibrationRun::default());
+}
+
+/// Samples the axes and moves through the steps on every button press. The
+/// finished calibration is used and saved right away.
+pub fn calibrate(
+    mut commands: Commands,
+    mut run: ResMut<CalibrationRun>,
+    mut joystick: NonSendMut<JoyStickResource>,
+    store: Option<NonSendMut<SettingsStoreResource>>,
+    actions: Res<InputActions>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let joystick = &mut *joystick;
+    let mut axes = [Some(&mut joystick.axis), joystick.cross_axis.as_mut()];
+
+    for (index, axis) in axes.iter_mut().enumerate() {
+        run.reading[index] = axis.as_mut().and_then(|axis| axis.read());
+    }
+    if run.step == CalibrationStep::Extremes {
+        let CalibrationRun { reading, axes, .. } = &mut *run;
+        for (reading, axis) in reading.iter().zip(axes.iter_mut()) {
+            if let Some(reading) = *reading {
+                axis.low = axis.low.min(reading);
+                axis.high = axis.high.max(reading);
+            }
+        }
+    }
+
+    if !actions.confirm {
+        return;
+    }
+
+    match run.step {
+        CalibrationStep::Center => {
+            for (index, axis) in axes.iter_mut().enumerate() {
+                let center = axis.as_mut().and_then(|axis| average(&mut ***axis));
+                run.axes[index] = match center {
+                    Some(center) => AxisCalibration {
+                        center,
+                        low: center,
+                        high: center,
+                    },
+                    None => AxisCalibration::default(),
+                };
+            }
+            run.step = CalibrationStep::Extremes;
+        }
+        CalibrationStep::Extremes => {
+            let has_cross_axis = run.reading[1].is_some();
+            let calibration = JoystickCalibration {
+                paddle: run.axes[0],
+                cross: if has_cross_axis {
+                    run.axes[1]
+                } else {
+                    AxisCalibration::default()
+                },
+            };
+            if calibration.paddle.travel() < MIN_TRAVEL
+                || (has_cross_axis && calibration.cross.travel() < MIN_TRAVEL)
+            {
+                *run = CalibrationRun {
+                    failed: true,
+                    ..default()
+                };
+                return;
+            }
+
+            if let Some(mut store) = store {
+                if !store.store.save(&calibration.to_bytes()) {
+                    #[cfg(feature = "esp32")]
+                    defmt::warn!("failed to save joystick calibration");
+                }
+            }
+            commands.insert_resource(calibration);
+            next_state.set(GameState::MainMenu);
+        }
+    }
+}
+
+/// Mean of a few readings of `axis`, `None` if none could be read.
+fn average(axis: &mut dyn super::hal::PaddleAxis) -> Option<u16> {
+    let (sum, count) = (0..CENTER_SAMPLES)
+        .filter_map(|_| axis.read())
+        .fold((0u32, 0u32), |(sum, count), reading| {
+            (sum + u32::from(reading), count + 1)
+        });
+    (count > 0).then(|| (sum / count) as u16)
+}
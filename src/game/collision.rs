@@ -0,0 +1,234 @@
+//! Collision detection shared by everything the ball can hit.
+//!
+//! Walls, the paddle and blocks are all plain [`Collider`]s. Bodies marked
+//! [`Bouncy`] are moved by [`move_and_collide`], which bounces them off every
+//! other collider and reports each contact as a [`Collision`] event. What a hit
+//! means for the game (aiming off the paddle, damaging a block) is handled by
+//! the systems reading those events.
+
+use bevy::math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume};
+use bevy::prelude::*;
+use embedded_graphics::prelude::Size;
+
+use super::{broadphase::Broadphase, resources::DisplayResolution, Position, Velocity};
+
+/// Most bounces a single body can make within one tick.
+const MAX_CONTACTS_PER_TICK: usize = 4;
+/// How far the walls reach beyond the screen, so nothing can get past them.
+const WALL_THICKNESS: f32 = 1000.0;
+
+/// Solid box covering `size` pixels from the entity's [`Position`].
+#[derive(Component, Clone, Copy, Debug)]
+#[require(Position)]
+pub struct Collider {
+    pub half_size: Vec2,
+}
+
+impl Collider {
+    pub fn new(size: Size) -> Self {
+        Self {
+            half_size: Vec2::new(size.width as f32, size.height as f32) / 2.0,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.half_size * 2.0
+    }
+
+    /// Bounding box of the collider placed at `position`.
+    pub fn aabb(&self, position: &Position) -> Aabb2d {
+        Aabb2d::new(position.0 + self.half_size, self.half_size)
+    }
+}
+
+/// Collider that moves with its [`Velocity`] and bounces off all other colliders.
+#[derive(Component, Default)]
+#[require(Velocity)]
+pub struct Bouncy;
+
+/// Edge of the screen the ball bounces off.
+#[derive(Component)]
+pub struct Wall;
+
+/// Contact between the moving body `a` and the collider `b`.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct Collision {
+    pub a: Entity,
+    pub b: Entity,
+    /// Normal of the surface of `b` that was hit, pointing towards `a`.
+    pub normal: Vec2,
+    /// How far `a` had to be pushed out of `b`. Zero when the contact was found
+    /// on the way, before the two overlapped.
+    pub depth: f32,
+}
+
+/// Spawns the left, right and top edges of the screen. The bottom is open,
+/// that's where balls get lost.
+pub fn spawn_walls(mut commands: Commands, display_resolution: NonSend<DisplayResolution>) {
+    let width = display_resolution.width as f32;
+    let height = display_resolution.height as f32;
+    let tall = Vec2::new(WALL_THICKNESS, height + 2.0 * WALL_THICKNESS);
+    let wide = Vec2::new(width + 2.0 * WALL_THICKNESS, WALL_THICKNESS);
+
+    for (position, size) in [
+        (Vec2::new(-WALL_THICKNESS, -WALL_THICKNESS), tall),
+        (Vec2::new(width, -WALL_THICKNESS), tall),
+        (Vec2::new(-WALL_THICKNESS, -WALL_THICKNESS), wide),
+    ] {
+        commands.spawn((
+            Wall,
+            Position(position),
+            Collider {
+                half_size: size / 2.0,
+            },
+        ));
+    }
+}
+
+/// Moves every bouncy body along its path for this tick.
+///
+/// A body overlapping a collider (e.g. the paddle moved into the ball) is first
+/// pushed out. After that collisions are swept: the body stops at the earliest
+/// contact on its way, bounces and carries on with the rest of its motion. Fast
+/// bodies therefore can't tunnel through thin colliders, and each contact is
+/// reported exactly once.
+///
+/// Static colliders are looked up in the [`Broadphase`] around the body, moving
+/// ones like the paddle are always checked.
+#[allow(clippy::type_complexity)]
+pub fn move_and_collide(
+    bodies: Query<(Entity, &mut Position, &mut Velocity, &Collider), With<Bouncy>>,
+    statics: Query<(Entity, &Position, &Collider), Without<Velocity>>,
+    movers: Query<(Entity, &Position, &Collider), (With<Velocity>, Without<Bouncy>)>,
+    broadphase: Res<Broadphase>,
+    time: Res<Time>,
+    mut collisions: EventWriter<Collision>,
+) {
+    let near = |area: &Aabb2d| {
+        let nearby = broadphase.query(area);
+        let movers = movers.iter();
+        nearby
+            .into_iter()
+            .filter_map(|entity| statics.get(entity).ok())
+            .chain(movers)
+            .map(|(entity, position, collider)| (entity, collider.aabb(position)))
+    };
+
+    for (body, mut position, mut velocity, collider) in bodies {
+        for (other, other_aabb) in near(&collider.aabb(&position)) {
+            let aabb = collider.aabb(&position);
+            let Some((normal, depth)) = penetration(&aabb, &other_aabb) else {
+                continue;
+            };
+
+            position.0 += normal * depth;
+            velocity.0 = reflect(velocity.0, normal);
+            collisions.write(Collision {
+                a: body,
+                b: other,
+                normal,
+                depth,
+            });
+        }
+
+        let mut remaining = time.delta_secs();
+        for _ in 0..MAX_CONTACTS_PER_TICK {
+            let aabb = collider.aabb(&position);
+            let motion = velocity.0 * remaining;
+            let path = aabb.merge(&Aabb2d {
+                min: aabb.min + motion,
+                max: aabb.max + motion,
+            });
+
+            let earliest = near(&path)
+                .filter_map(|(other, other_aabb)| {
+                    sweep(&aabb, motion, &other_aabb).map(|(time, normal)| (time, normal, other))
+                })
+                .min_by(|(a, ..), (b, ..)| a.total_cmp(b));
+
+            let Some((time_of_impact, normal, other)) = earliest else {
+                position.0 += motion;
+                break;
+            };
+
+            position.0 += motion * time_of_impact;
+            remaining *= 1.0 - time_of_impact;
+            velocity.0 = reflect(velocity.0, normal);
+            collisions.write(Collision {
+                a: body,
+                b: other,
+                normal,
+                depth: 0.0,
+            });
+        }
+    }
+}
+
+/// Earliest contact of `moving` travelling by `motion` with the still `target`.
+///
+/// Returns the fraction of `motion` at which the two touch, and the normal of
+/// the surface of `target` that was hit. Boxes that already overlap or only
+/// slide along each other don't count as a contact.
+fn sweep(moving: &Aabb2d, motion: Vec2, target: &Aabb2d) -> Option<(f32, Vec2)> {
+    // Grow the target by the size of the moving box, so only the path of its
+    // centre needs to be checked.
+    let target = target.grow(moving.half_size());
+    let origin = moving.center();
+
+    let (entry_x, exit_x) = slab(origin.x, motion.x, target.min.x, target.max.x)?;
+    let (entry_y, exit_y) = slab(origin.y, motion.y, target.min.y, target.max.y)?;
+
+    let entry = entry_x.max(entry_y);
+    let exit = exit_x.min(exit_y);
+    if entry >= exit || !(0.0..1.0).contains(&entry) {
+        return None;
+    }
+
+    let normal = if entry_x > entry_y {
+        Vec2::new(-motion.x.signum(), 0.0)
+    } else {
+        Vec2::new(0.0, -motion.y.signum())
+    };
+    Some((entry, normal))
+}
+
+/// Times at which `origin + t * motion` enters and leaves the open range
+/// `min..max` on one axis.
+fn slab(origin: f32, motion: f32, min: f32, max: f32) -> Option<(f32, f32)> {
+    if motion == 0.0 {
+        return (min < origin && origin < max).then_some((f32::NEG_INFINITY, f32::INFINITY));
+    }
+
+    let to_min = (min - origin) / motion;
+    let to_max = (max - origin) / motion;
+    Some((to_min.min(to_max), to_min.max(to_max)))
+}
+
+/// Shortest way out of `b` for the overlapping `a`, as the push direction and
+/// distance. Boxes that merely touch don't overlap.
+fn penetration(a: &Aabb2d, b: &Aabb2d) -> Option<(Vec2, f32)> {
+    if !a.intersects(b) {
+        return None;
+    }
+
+    let overlap = a.max.min(b.max) - a.min.max(b.min);
+    if overlap.x <= 0.0 || overlap.y <= 0.0 {
+        return None;
+    }
+
+    let away = (a.center() - b.center()).signum();
+    if overlap.x > overlap.y {
+        Some((Vec2::new(0.0, away.y), overlap.y))
+    } else {
+        Some((Vec2::new(away.x, 0.0), overlap.x))
+    }
+}
+
+/// Turns `velocity` away from a surface with `normal`.
+fn reflect(velocity: Vec2, normal: Vec2) -> Vec2 {
+    if normal.x != 0.0 {
+        Vec2::new(normal.x * velocity.x.abs(), velocity.y)
+    } else {
+        Vec2::new(velocity.x, normal.y * velocity.y.abs())
+    }
+}
@@ -0,0 +1,69 @@
+//! Input backend for a rotary encoder, like the paddle controllers of old
+//! arcade cabinets.
+//!
+//! Every detent moves the paddle by a fixed distance instead of setting its
+//! speed, so the knob's position is the paddle's. Turning further at a screen
+//! edge is lost, turning back moves the paddle away at once. The push switch
+//! confirms and holding it down pauses, like the joystick button.
+
+use bevy::prelude::*;
+
+use super::{
+    actions::InputActions,
+    button::{ButtonLongPress, ButtonPressed},
+    resources::EncoderResource,
+    state::GameState,
+};
+
+/// How turning the encoder moves the paddle.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct EncoderSettings {
+    /// Counts the encoder reports from one detent to the next.
+    pub counts_per_detent: i32,
+    /// Pixels the paddle moves for each detent, negative to turn the other way.
+    pub pixels_per_detent: f32,
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        Self {
+            counts_per_detent: 4,
+            pixels_per_detent: 4.0,
+        }
+    }
+}
+
+/// Counts turned but not yet making up a whole detent.
+#[derive(Resource, Default)]
+pub struct EncoderInput {
+    counts: i32,
+}
+
+/// Fills [`InputActions`] from the encoder and its switch. As with the
+/// joystick, a hold only pauses if its press left the state alone.
+#[allow(clippy::too_many_arguments)]
+pub fn read_encoder(
+    mut encoder: NonSendMut<EncoderResource>,
+    settings: Res<EncoderSettings>,
+    state: Res<State<GameState>>,
+    mut input: ResMut<EncoderInput>,
+    mut pressed: EventReader<ButtonPressed>,
+    mut long_press: EventReader<ButtonLongPress>,
+    mut actions: ResMut<InputActions>,
+    mut pressed_in: Local<Option<GameState>>,
+) {
+    let counts_per_detent = settings.counts_per_detent.max(1);
+    input.counts += encoder.encoder.take_counts();
+    // Rounds towards zero, the rest waits for the next frame.
+    let detents = input.counts / counts_per_detent;
+    input.counts -= detents * counts_per_detent;
+
+    actions.move_by += detents as f32 * settings.pixels_per_detent;
+    if pressed.read().count() > 0 {
+        actions.confirm = true;
+        *pressed_in = Some(*state.get());
+    }
+    if long_press.read().count() > 0 && *pressed_in == Some(*state.get()) {
+        actions.pause = true;
+    }
+}
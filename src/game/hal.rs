@@ -0,0 +1,70 @@
+//! Hardware abstraction used by the game systems.
+//!
+//! Gameplay only talks to these traits, so the board specific bindings (ADC
+//! pins, GPIO, pulse counter, I2C sensors, UART, RNG, flash and the OLED driver)
+//! live in the firmware binary and the game itself builds for any target,
+//! including an x86 host.
+
+use core::fmt::Debug;
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::DrawTarget};
+
+/// Analog axis that steers the paddle.
+pub trait PaddleAxis {
+    /// Reads the raw 12-bit ADC value of the axis, or `None` if the read failed.
+    fn read(&mut self) -> Option<u16>;
+}
+
+/// Push button, e.g. the joystick switch.
+pub trait Button {
+    fn is_pressed(&mut self) -> bool;
+}
+
+/// Quadrature rotary encoder, e.g. counted by a pulse counter.
+pub trait RotaryEncoder {
+    /// Counts turned since the last call, positive clockwise.
+    fn take_counts(&mut self) -> i32;
+}
+
+/// Sensor telling how far a controller is tilted, e.g. an accelerometer.
+pub trait TiltSensor {
+    /// Roll angle in radians, or `None` if the read failed.
+    fn read_roll(&mut self) -> Option<f32>;
+}
+
+/// Byte stream such as a UART, read without blocking.
+pub trait SerialPort {
+    /// Next byte received, `None` if nothing is waiting.
+    fn read_byte(&mut self) -> Option<u8>;
+}
+
+/// Small block of non-volatile memory, e.g. a flash sector, for settings that
+/// have to survive a reboot.
+pub trait SettingsStore {
+    /// Fills `bytes` from the start of the store. Returns whether that worked.
+    fn load(&mut self, bytes: &mut [u8]) -> bool;
+    /// Writes `bytes` to the start of the store. Returns whether that worked.
+    fn save(&mut self, bytes: &[u8]) -> bool;
+}
+
+/// Source of random numbers for spawning balls.
+pub trait RandomSource {
+    fn next_u32(&mut self) -> u32;
+}
+
+/// Monochrome buffered display the game renders into.
+pub trait GameDisplay: DrawTarget<Color = BinaryColor, Error: Debug> + 'static {
+    /// Sends the buffered frame to the screen.
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "ssd1306")]
+impl<DI, SIZE> GameDisplay for ssd1306::Ssd1306<DI, SIZE, ssd1306::mode::BufferedGraphicsMode<SIZE>>
+where
+    DI: ssd1306::prelude::WriteOnlyDataCommand + 'static,
+    SIZE: ssd1306::size::DisplaySize + 'static,
+{
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        ssd1306::Ssd1306::flush(self)
+    }
+}
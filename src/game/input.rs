@@ -1,41 +1,109 @@
 use bevy::prelude::*;
 
 use super::{
-    player::{Player, PLAYER_SIZE, PLAYER_SPEED},
-    resources::{AdcResource, DisplayResolution, GameState, GameStatus, JoyStickResource},
-    state::ResetGameEvent,
-    Position,
+    actions::InputActions,
+    ball::{Serving, AIM_SPEED},
+    collision::Collider,
+    player::{Player, PLAYER_SPEED},
+    resources::DisplayResolution,
+    state::{GameState, PauseMenu, PauseMenuItem},
+    Position, Velocity,
 };
 
-pub fn joystick(
-    mut joystick: NonSendMut<JoyStickResource>,
-    mut adc_res: NonSendMut<AdcResource>,
-    mut player: Query<&mut Position, With<Player>>,
+/// How far the paddle has to be steered to move through a menu.
+const MENU_DEFLECTION: f32 = 0.5;
+
+/// Moves the paddle as fast or as far as asked for. A ball waiting to be served
+/// moves along, and its aim turns the same way as the paddle.
+pub fn move_paddle(
+    mut actions: ResMut<InputActions>,
+    mut player: Query<(&mut Position, &mut Velocity, &Collider), With<Player>>,
+    mut serving: Query<&mut Serving>,
     display_resolution: NonSendMut<DisplayResolution>,
+    time: Res<Time>,
 ) {
-    let Ok(mut position) = player.single_mut() else {
+    let Ok((mut position, mut velocity, collider)) = player.single_mut() else {
         return;
     };
+    velocity.0 = Vec2::ZERO;
+    let move_by = core::mem::take(&mut actions.move_by);
+
+    let start_x = position.0.x;
+    let right_edge = display_resolution.width as f32 - collider.size().x;
+    position.0.x = (position.0.x + actions.move_axis * PLAYER_SPEED * time.delta_secs() + move_by)
+        .clamp(0.0, right_edge.max(0.0));
+
+    // Moving the paddle by some distance turns the aim as much as steering it
+    // there at full speed would.
+    let turn = (actions.move_axis.clamp(-1.0, 1.0) * time.delta_secs() + move_by / PLAYER_SPEED)
+        * AIM_SPEED;
+    serving.iter_mut().for_each(|mut serving| serving.aim(turn));
+
+    // How fast the paddle actually moved, it stops at the screen edges.
+    if time.delta_secs() > 0.0 {
+        velocity.0.x = (position.0.x - start_x) / time.delta_secs();
+    }
+}
 
-    let Ok(adc_value): Result<u16, _> = nb::block!(adc_res.adc.read_oneshot(&mut joystick.vry_pin))
-    else {
+/// Starts a new campaign from any of the menu screens.
+pub fn start_btn(actions: Res<InputActions>, mut next_state: ResMut<NextState<GameState>>) {
+    if actions.confirm {
+        next_state.set(GameState::LevelIntro);
+    }
+}
+
+/// Launches the balls waiting on the paddle, or pauses the game when there are
+/// none. Pausing works at any time.
+pub fn play_btn(
+    actions: Res<InputActions>,
+    mut serving: Query<&mut Serving>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.pause || (actions.confirm && serving.is_empty()) {
+        next_state.set(GameState::Paused);
         return;
-    };
+    }
 
-    if adc_value > 3000 {
-        position.0.x = (position.0.x - PLAYER_SPEED).max(0);
-    } else if adc_value < 1500 {
-        let right_edge = display_resolution.width as i32 - PLAYER_SIZE.width as i32;
-        position.0.x = (position.0.x + PLAYER_SPEED).min(right_edge);
+    if actions.confirm {
+        serving
+            .iter_mut()
+            .for_each(|mut serving| serving.launch = true);
     }
 }
 
-pub fn reset_btn(
-    joystick: NonSendMut<JoyStickResource>,
-    mut event_writer: EventWriter<ResetGameEvent>,
-    game_status: ResMut<GameStatus>,
+/// Moves through the pause menu one entry for each push of the stick or turn of
+/// the encoder, and picks the entry on confirm. Back or pause again resumes.
+pub fn pause_menu(
+    actions: Res<InputActions>,
+    mut menu: ResMut<PauseMenu>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut pushed: Local<bool>,
 ) {
-    if joystick.btn.is_low() && game_status.state != GameState::Playing {
-        event_writer.write(ResetGameEvent);
+    let direction = if actions.move_axis <= -MENU_DEFLECTION {
+        -1
+    } else if actions.move_axis >= MENU_DEFLECTION {
+        1
+    } else {
+        0
+    };
+    if direction != 0 && !*pushed {
+        menu.select(direction);
+    }
+    *pushed = direction != 0;
+    if actions.move_by != 0.0 {
+        menu.select(if actions.move_by < 0.0 { -1 } else { 1 });
+    }
+
+    if actions.back || actions.pause {
+        next_state.set(GameState::Playing);
+        return;
+    }
+    if !actions.confirm {
+        return;
     }
+    next_state.set(match menu.selected {
+        PauseMenuItem::Resume => GameState::Playing,
+        PauseMenuItem::Restart => GameState::LevelIntro,
+        PauseMenuItem::Quit => GameState::MainMenu,
+    });
 }
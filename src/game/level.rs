@@ -0,0 +1,25 @@
+//! Levels of the campaign. They are written as text files under `levels/` and
+//! compiled into [`LEVELS`] by `build.rs`.
+
+/// How long the "Level N" screen is shown before the round starts.
+pub const LEVEL_INTRO_SECS: f32 = 1.5;
+
+use super::block::BrickKind;
+
+/// One block of a level's grid, counted from the top left.
+pub struct LevelBlock {
+    pub column: u8,
+    pub row: u8,
+    pub kind: BrickKind,
+}
+
+pub struct Level {
+    pub name: &'static str,
+    /// Ball speed in pixels per second, if the level changes it.
+    pub ball_speed: Option<f32>,
+    /// Paddle width in pixels, if the level changes it.
+    pub paddle_width: Option<u32>,
+    pub blocks: &'static [LevelBlock],
+}
+
+include!(concat!(env!("OUT_DIR"), "/levels.rs"));
@@ -1,101 +1,343 @@
-mod ball;
-mod block;
+pub mod actions;
+pub mod ball;
+pub mod block;
+pub mod broadphase;
+pub mod button;
+pub mod calibration;
+pub mod collision;
+pub mod encoder;
+pub mod hal;
 mod input;
-mod player;
+pub mod level;
+pub mod player;
+pub mod powerup;
 mod render;
 pub mod resources;
-mod state;
+pub mod runner;
+pub mod serial;
+pub mod state;
+pub mod stick;
+pub mod tilt;
+
+use core::marker::PhantomData;
 
 use bevy::prelude::*;
-use defmt::info;
-use esp_hal::time::Duration;
+use bevy::state::app::StatesPlugin;
 
 use bevy_ecs::component::Component;
 use embedded_graphics::prelude::Point;
-use resources::{GameState, GameStatus};
+use hal::GameDisplay;
+use resources::GameStatus;
+use state::GameState;
 
+/// Velocity in pixels per second.
 #[derive(Component, Default)]
-pub struct Velocity {
-    pub x: i32,
-    pub y: i32,
-}
+pub struct Velocity(pub Vec2);
 
+/// Top left corner of an entity, in (sub-)pixels.
 #[derive(Component, Default)]
-pub struct Position(pub Point);
+pub struct Position(pub Vec2);
 
-pub fn start_game(mut app: App) -> ! {
-    app.insert_resource(GameStatus::default())
-        .add_event::<state::ResetGameEvent>()
-        .add_systems(
-            Update,
-            (
-                // Handle input
-                (input::joystick, input::reset_btn).chain(),
-                // Playing
+impl Position {
+    /// Nearest whole pixel, for drawing.
+    pub fn pixel(&self) -> Point {
+        let pixel = self.0.round().as_ivec2();
+        Point::new(pixel.x, pixel.y)
+    }
+}
+
+/// Rate of the fixed gameplay tick, independent of how fast frames are drawn.
+pub const SIMULATION_HZ: f64 = 120.0;
+
+/// Stages of a game update. The input backend fills
+/// [`actions::InputActions`] in `PreUpdate`. Gameplay runs on the fixed tick in
+/// the order `Input`, `Simulation`, `Collision`, `Cleanup`, the last three only
+/// while playing. Each frame then acts on the menu input and draws in `Render`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameSet {
+    /// Reads the player's controls, or acts on the actions read from them.
+    Input,
+    /// Spawns and updates entities before they move.
+    Simulation,
+    /// Moves balls and responds to what they hit.
+    Collision,
+    /// Removes lost balls and destroyed blocks and checks for the end of a round.
+    Cleanup,
+    /// Draws the frame.
+    Render,
+}
+
+/// Game state, entities and the gameplay systems.
+///
+/// The game only reacts to [`actions::InputActions`], which an input backend
+/// like [`BreakoutInputPlugin`], [`EncoderInputPlugin`], [`TiltInputPlugin`] or
+/// [`SerialInputPlugin`] fills in. Rendering
+/// comes from [`BreakoutRenderPlugin`] or whatever replaces it. The platform
+/// resources (display, random source and whatever the input backend reads)
+/// have to be inserted by the caller.
+pub struct BreakoutPlugin;
+
+impl Plugin for BreakoutPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<StatesPlugin>() {
+            app.add_plugins(StatesPlugin);
+        }
+
+        app.insert_resource(GameStatus::default())
+            .insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ))
+            .init_state::<GameState>()
+            .init_resource::<ball::BallCollisions>()
+            .init_resource::<state::PauseMenu>()
+            .init_resource::<powerup::ActivePowerUps>()
+            .init_resource::<powerup::CapsuleDrops>()
+            .init_resource::<actions::InputActions>()
+            .add_event::<collision::Collision>()
+            .add_event::<powerup::PowerUpCollected>()
+            .configure_sets(
+                FixedUpdate,
                 (
-                    ball::spawn_ball_if_empty,
-                    ball::update_ball,
-                    ball::collison_handle,
-                    ball::remove_balls,
-                    block::remove_blocks,
-                    state::update_game_state,
+                    GameSet::Input,
+                    (GameSet::Simulation, GameSet::Collision, GameSet::Cleanup)
+                        .chain()
+                        .run_if(in_state(GameState::Playing)),
                 )
-                    .run_if(run_if_playing)
-                    .chain()
-                    .after(input::reset_btn),
-                // Reset the game and spawn
-                state::reset_game,
+                    .chain(),
+            )
+            .configure_sets(Update, (GameSet::Input, GameSet::Render).chain())
+            .add_systems(
+                Startup,
+                (collision::spawn_walls, broadphase::setup_broadphase),
+            )
+            .add_systems(PreUpdate, actions::clear_actions.before(GameSet::Input))
+            .add_systems(
+                FixedUpdate,
+                input::move_paddle
+                    .run_if(not(
+                        in_state(GameState::Paused).or(in_state(GameState::Calibrating))
+                    ))
+                    .in_set(GameSet::Input),
+            )
+            .add_systems(
+                Update,
+                (
+                    input::start_btn.run_if(
+                        in_state(GameState::MainMenu)
+                            .or(in_state(GameState::GameOver))
+                            .or(in_state(GameState::CampaignCompleted)),
+                    ),
+                    input::play_btn.run_if(in_state(GameState::Playing)),
+                    input::pause_menu.run_if(in_state(GameState::Paused)),
+                )
+                    .in_set(GameSet::Input),
+            )
+            .add_systems(OnExit(GameState::MainMenu), state::new_campaign)
+            .add_systems(OnExit(GameState::GameOver), state::new_campaign)
+            .add_systems(OnExit(GameState::CampaignCompleted), state::new_campaign)
+            .add_systems(
+                OnEnter(GameState::LevelIntro),
                 (
-                    ball::spawn_ball_on_reset,
+                    state::despawn_level,
                     block::spawn_blocks,
+                    powerup::clear_power_ups,
                     player::spawn_player,
-                    state::switch_reset,
+                    state::start_level_intro,
                 )
-                    .chain()
-                    .run_if(run_if_resetting),
-                // .after(state::reset_game),
-                // Rendering
-                render::clear_screen,
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                state::tick_level_intro.run_if(in_state(GameState::LevelIntro)),
+            )
+            .add_systems(OnEnter(GameState::Paused), state::open_pause_menu)
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Paused,
+                    entered: GameState::LevelIntro,
+                },
+                (state::despawn_round, state::new_campaign),
+            )
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Paused,
+                    entered: GameState::MainMenu,
+                },
+                state::despawn_round,
+            )
+            .add_systems(OnEnter(GameState::GameOver), state::despawn_round)
+            .add_systems(OnEnter(GameState::CampaignCompleted), state::despawn_round)
+            .add_systems(
+                FixedUpdate,
                 (
-                    render::print_lives,
-                    render::print_score,
-                    render::render_game,
+                    (
+                        (ball::spawn_ball_if_empty, ball::serve_balls).chain(),
+                        (
+                            powerup::fall_capsules,
+                            powerup::collect_capsules,
+                            powerup::apply_power_ups,
+                            powerup::tick_power_ups,
+                            powerup::resize_paddle,
+                            powerup::carry_caught_balls,
+                        )
+                            .chain(),
+                    )
+                        .in_set(GameSet::Simulation),
+                    (
+                        broadphase::update_broadphase,
+                        collision::move_and_collide,
+                        ball::collide_balls,
+                        ball::aim_off_paddle,
+                        powerup::catch_balls,
+                        block::damage_blocks,
+                    )
+                        .chain()
+                        .in_set(GameSet::Collision),
+                    (
+                        ball::remove_balls,
+                        powerup::drop_capsules,
+                        block::remove_blocks,
+                        state::update_game_state,
+                    )
+                        .chain()
+                        .in_set(GameSet::Cleanup),
+                ),
+            );
+    }
+}
+
+/// Input backend for the joystick module: the stick steers the paddle, its
+/// button confirms and holding the button down pauses.
+///
+/// How the axis maps to paddle speed comes from the [`stick::StickSettings`]
+/// resource, insert one to change the defaults. The same goes for debouncing
+/// the button and the timing of its gestures with [`button::ButtonTimings`].
+/// With a [`resources::SettingsStoreResource`] the joystick calibration is
+/// loaded from it at startup, or taken on the calibration screen and saved
+/// there.
+pub struct BreakoutInputPlugin;
+
+impl Plugin for BreakoutInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(button::ButtonPlugin)
+            .init_resource::<stick::StickSettings>()
+            .init_resource::<stick::JoystickCalibration>()
+            .add_systems(Startup, calibration::check_calibration)
+            .add_systems(
+                OnEnter(GameState::Calibrating),
+                calibration::start_calibration,
+            )
+            .add_systems(
+                PreUpdate,
+                (
+                    button::read_button::<resources::JoyStickResource>,
+                    stick::read_joystick,
                 )
-                    .run_if(run_if_playing)
                     .chain()
-                    .after(render::clear_screen),
-                render::display_welcome
-                    .run_if(run_if_main_menu)
-                    .after(render::clear_screen),
-                render::display_game_over.run_if(run_if_game_over),
-                render::display_game_completed.run_if(run_if_completed),
-            ),
-        );
-    info!("running app");
-    app.run();
-    loop {
-        // info!("updating game");
-        app.update();
-        super::blocking_delay(Duration::from_millis(50));
+                    .in_set(GameSet::Input),
+            )
+            .add_systems(
+                Update,
+                calibration::calibrate
+                    .run_if(in_state(GameState::Calibrating))
+                    .in_set(GameSet::Input),
+            );
     }
 }
 
-fn run_if_playing(game_status: Res<GameStatus>) -> bool {
-    game_status.state == GameState::Playing
+/// Input backend for a rotary encoder with a push switch, read from the
+/// [`resources::EncoderResource`]. Each detent moves the paddle by the distance
+/// set in [`encoder::EncoderSettings`].
+pub struct EncoderInputPlugin;
+
+impl Plugin for EncoderInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(button::ButtonPlugin)
+            .init_resource::<encoder::EncoderSettings>()
+            .init_resource::<encoder::EncoderInput>()
+            .add_systems(
+                PreUpdate,
+                (
+                    button::read_button::<resources::EncoderResource>,
+                    encoder::read_encoder,
+                )
+                    .chain()
+                    .in_set(GameSet::Input),
+            );
+    }
 }
 
-fn run_if_main_menu(game_status: Res<GameStatus>) -> bool {
-    game_status.state == GameState::MainMenu
+/// Input backend steering by tilt, read from the [`resources::TiltResource`].
+/// Holding the button down takes the current tilt as level, a short press
+/// confirms when let go, see [`tilt`].
+pub struct TiltInputPlugin;
+
+impl Plugin for TiltInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(button::ButtonPlugin)
+            .init_resource::<tilt::TiltSettings>()
+            .init_resource::<tilt::TiltInput>()
+            .add_systems(
+                PreUpdate,
+                (
+                    button::read_button::<resources::TiltResource>,
+                    tilt::read_tilt,
+                )
+                    .chain()
+                    .in_set(GameSet::Input),
+            );
+    }
 }
 
-fn run_if_game_over(game_status: Res<GameStatus>) -> bool {
-    game_status.state == GameState::GameOver
+/// Input backend reading text commands from the
+/// [`resources::SerialResource`], see [`serial`] for the commands.
+pub struct SerialInputPlugin;
+
+impl Plugin for SerialInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<serial::SerialInput>()
+            .add_systems(PreUpdate, serial::read_serial.in_set(GameSet::Input));
+    }
 }
 
-fn run_if_completed(game_status: Res<GameStatus>) -> bool {
-    game_status.state == GameState::LevelCompleted
+/// Draws the game and its menus onto the display `D`.
+pub struct BreakoutRenderPlugin<D>(PhantomData<fn() -> D>);
+
+impl<D> Default for BreakoutRenderPlugin<D> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
 }
 
-fn run_if_resetting(game_status: Res<GameStatus>) -> bool {
-    game_status.state == GameState::Resetting
+impl<D: GameDisplay> Plugin for BreakoutRenderPlugin<D> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                render::clear_screen::<D>.run_if(not(in_state(GameState::Paused))),
+                (
+                    render::print_lives::<D>,
+                    render::print_score::<D>,
+                    render::print_level::<D>,
+                    render::print_power_ups::<D>,
+                    render::print_get_ready::<D>.run_if(resource_exists::<ball::RespawnTimer>),
+                    render::render_game::<D>,
+                )
+                    .run_if(in_state(GameState::Playing))
+                    .chain()
+                    .after(render::clear_screen::<D>),
+                render::display_welcome::<D>
+                    .run_if(in_state(GameState::MainMenu))
+                    .after(render::clear_screen::<D>),
+                render::display_level_intro::<D>
+                    .run_if(in_state(GameState::LevelIntro))
+                    .after(render::clear_screen::<D>),
+                render::display_pause_menu::<D>.run_if(in_state(GameState::Paused)),
+                render::display_game_over::<D>.run_if(in_state(GameState::GameOver)),
+                render::display_game_completed::<D>.run_if(in_state(GameState::CampaignCompleted)),
+                render::display_calibration::<D>
+                    .run_if(in_state(GameState::Calibrating))
+                    .after(render::clear_screen::<D>),
+            )
+                .in_set(GameSet::Render),
+        );
+    }
 }
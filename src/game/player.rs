@@ -1,34 +1,59 @@
 use bevy::prelude::*;
-use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::prelude::Size;
 
-use super::{resources::DisplayResolution, state::ResetGameEvent, Position};
+use super::{
+    collision::Collider,
+    level::LEVELS,
+    resources::{DisplayResolution, GameStatus},
+    Position, Velocity,
+};
 
-pub const PLAYER_SPEED: i32 = 5;
+/// Paddle speed at full stick deflection in pixels per second, unless
+/// `StickSettings` sets its own.
+pub const PLAYER_SPEED: f32 = 100.0;
+/// Paddle size, unless the level sets its own width.
 pub const PLAYER_SIZE: Size = Size::new(40, 5);
 const PLAYER_LIVES: u8 = 3;
+/// Most lives the player can collect, that's all the HUD has room for.
+pub const PLAYER_MAX_LIVES: u8 = 4;
 
 #[derive(Component)]
-#[require(Position)]
+#[require(Position, Velocity)]
 pub struct Player {
     pub lives: u8,
 }
 
+/// Centres the paddle for a new level, spawning it with full lives at the start
+/// of a campaign.
 pub fn spawn_player(
     mut commands: Commands,
+    mut player: Query<(&mut Position, &mut Collider), With<Player>>,
     display_resolution: NonSendMut<DisplayResolution>,
-    mut events: EventReader<ResetGameEvent>,
+    game_status: Res<GameStatus>,
 ) {
-    let Some(_) = events.read().next() else {
+    let width = paddle_width(game_status.level);
+    let size = Size::new(width, PLAYER_SIZE.height);
+    let start = Vec2::new(
+        (display_resolution.width / 2) as f32 - (width / 2) as f32,
+        (display_resolution.height - size.height) as f32,
+    );
+
+    if let Ok((mut position, mut collider)) = player.single_mut() {
+        position.0 = start;
+        *collider = Collider::new(size);
         return;
-    };
+    }
 
     commands.spawn((
         Player {
             lives: PLAYER_LIVES,
         },
-        Position(Point::new(
-            (display_resolution.width / 2 - PLAYER_SIZE.width / 2) as i32,
-            (display_resolution.height - PLAYER_SIZE.height) as i32,
-        )),
+        Position(start),
+        Collider::new(size),
     ));
 }
+
+/// Width of the paddle on `level`, before any power-up.
+pub fn paddle_width(level: usize) -> u32 {
+    LEVELS[level].paddle_width.unwrap_or(PLAYER_SIZE.width)
+}
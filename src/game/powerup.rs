@@ -0,0 +1,370 @@
+//! Capsules dropped by destroyed blocks and the power-ups they give.
+//!
+//! Now and then a destroyed block drops a [`Capsule`]. It falls straight down
+//! and is collected by touching the paddle, capsules that miss it are lost.
+//! Wide paddle, slow ball and catch last for [`POWER_UP_SECS`], collecting one
+//! again restarts its time. Multi-ball and extra life take effect at once.
+
+use alloc::vec::Vec;
+use core::f32::consts::PI;
+
+use bevy::math::bounding::{BoundingVolume, IntersectsVolume};
+use bevy::prelude::*;
+use embedded_graphics::prelude::Size;
+
+use super::{
+    ball::{Ball, BALL_SIZE},
+    block::Block,
+    collision::{Collider, Collision},
+    player::{paddle_width, Player, PLAYER_MAX_LIVES, PLAYER_SIZE},
+    resources::{DisplayResolution, GameStatus, RandResource},
+    Position, Velocity,
+};
+
+pub const CAPSULE_SIZE: Size = Size::new(8, 8);
+/// Falling speed of capsules in pixels per second.
+const CAPSULE_SPEED: f32 = 20.0;
+/// One in this many destroyed blocks drops a capsule.
+const DROP_ONE_IN: u32 = 5;
+/// How long timed power-ups last.
+pub const POWER_UP_SECS: f32 = 10.0;
+/// How much wider the paddle gets.
+const WIDE_PADDLE_FACTOR: f32 = 1.5;
+/// How much the balls slow down.
+pub const SLOW_BALL_FACTOR: f32 = 0.6;
+/// How long a caught ball sits on the paddle before it is launched again.
+pub const CATCH_HOLD_SECS: f32 = 1.0;
+/// Angle between a ball and each of the two balls split off it.
+const MULTI_BALL_ANGLE: f32 = PI / 8.0;
+/// Multi-ball doesn't split off more balls once there are this many.
+const MAX_BALLS: usize = 6;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerUp {
+    WidePaddle,
+    SlowBall,
+    /// Splits every ball into three.
+    MultiBall,
+    /// Balls landing on the paddle stick to it for [`CATCH_HOLD_SECS`].
+    Catch,
+    ExtraLife,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 5] = [
+        PowerUp::WidePaddle,
+        PowerUp::SlowBall,
+        PowerUp::MultiBall,
+        PowerUp::Catch,
+        PowerUp::ExtraLife,
+    ];
+
+    /// Letter shown on the capsule and, while active, in the HUD.
+    pub fn letter(self) -> char {
+        match self {
+            PowerUp::WidePaddle => 'W',
+            PowerUp::SlowBall => 'S',
+            PowerUp::MultiBall => 'M',
+            PowerUp::Catch => 'C',
+            PowerUp::ExtraLife => 'L',
+        }
+    }
+}
+
+/// Falling capsule that gives `power_up` when it reaches the paddle.
+#[derive(Component)]
+#[require(Position, Velocity)]
+pub struct Capsule {
+    pub power_up: PowerUp,
+}
+
+/// Ball held on the paddle by the catch power-up.
+#[derive(Component)]
+pub struct Caught {
+    /// Distance from the left edge of the paddle.
+    offset: f32,
+    /// Velocity the ball is launched with once released.
+    velocity: Vec2,
+    timer: Timer,
+}
+
+/// Chance of a destroyed block dropping a capsule, one in `one_in`. Zero turns
+/// drops off.
+#[derive(Resource)]
+pub struct CapsuleDrops {
+    pub one_in: u32,
+}
+
+impl Default for CapsuleDrops {
+    fn default() -> Self {
+        Self {
+            one_in: DROP_ONE_IN,
+        }
+    }
+}
+
+/// Timed power-ups in effect, with the time they have left.
+#[derive(Resource, Default)]
+pub struct ActivePowerUps(pub Vec<(PowerUp, Timer)>);
+
+impl ActivePowerUps {
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.remaining_secs(power_up).is_some()
+    }
+
+    pub fn remaining_secs(&self, power_up: PowerUp) -> Option<f32> {
+        self.0
+            .iter()
+            .find(|(active, _)| *active == power_up)
+            .map(|(_, timer)| timer.remaining_secs())
+    }
+}
+
+/// Balls with the launch velocity of those that are caught.
+type Balls<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Position,
+        &'static mut Velocity,
+        Option<&'static mut Caught>,
+    ),
+    With<Ball>,
+>;
+
+/// The paddle caught a capsule.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct PowerUpCollected(pub PowerUp);
+
+/// Rolls for a capsule from every block destroyed this tick.
+pub fn drop_capsules(
+    mut commands: Commands,
+    blocks: Query<(&Block, &Position, &Collider)>,
+    drops: Res<CapsuleDrops>,
+    mut rand_res: NonSendMut<RandResource>,
+) {
+    if drops.one_in == 0 {
+        return;
+    }
+
+    let rng = &mut rand_res.rng;
+    for (block, position, collider) in &blocks {
+        if block.lives > 0 || rng.next_u32() > u32::MAX / drops.one_in {
+            continue;
+        }
+
+        let power_up = PowerUp::ALL[rng.next_u32() as usize % PowerUp::ALL.len()];
+        let center = collider.aabb(position).center();
+        commands.spawn((
+            Capsule { power_up },
+            Position(center - Collider::new(CAPSULE_SIZE).half_size),
+            Velocity(Vec2::new(0.0, CAPSULE_SPEED)),
+        ));
+    }
+}
+
+/// Lets capsules fall, removing the ones that left the screen.
+pub fn fall_capsules(
+    mut commands: Commands,
+    capsules: Query<(Entity, &mut Position, &Velocity), With<Capsule>>,
+    display_resolution: NonSend<DisplayResolution>,
+    time: Res<Time>,
+) {
+    for (entity, mut position, velocity) in capsules {
+        position.0 += velocity.0 * time.delta_secs();
+        if position.0.y > display_resolution.height as f32 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Picks up the capsules touching the paddle.
+pub fn collect_capsules(
+    mut commands: Commands,
+    capsules: Query<(Entity, &Position, &Capsule)>,
+    player: Query<(&Position, &Collider), With<Player>>,
+    mut collected: EventWriter<PowerUpCollected>,
+) {
+    let Ok((paddle_position, paddle_collider)) = player.single() else {
+        return;
+    };
+    let paddle = paddle_collider.aabb(paddle_position);
+
+    for (entity, position, capsule) in &capsules {
+        if Collider::new(CAPSULE_SIZE)
+            .aabb(position)
+            .intersects(&paddle)
+        {
+            collected.write(PowerUpCollected(capsule.power_up));
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Starts the effect of every power-up collected this tick.
+pub fn apply_power_ups(
+    mut commands: Commands,
+    mut collected: EventReader<PowerUpCollected>,
+    mut active: ResMut<ActivePowerUps>,
+    mut balls: Balls,
+    mut player: Query<&mut Player>,
+) {
+    for PowerUpCollected(power_up) in collected.read().copied() {
+        match power_up {
+            PowerUp::WidePaddle | PowerUp::SlowBall | PowerUp::Catch => {
+                let timer = Timer::from_seconds(POWER_UP_SECS, TimerMode::Once);
+                if let Some((_, running)) = active.0.iter_mut().find(|(p, _)| *p == power_up) {
+                    *running = timer;
+                    continue;
+                }
+
+                active.0.push((power_up, timer));
+                if power_up == PowerUp::SlowBall {
+                    scale_ball_speed(&mut balls, SLOW_BALL_FACTOR);
+                }
+            }
+            PowerUp::MultiBall => {
+                let mut count = balls.iter().count();
+                for (position, velocity, caught) in &balls {
+                    let velocity = caught.map_or(velocity.0, |caught| caught.velocity);
+                    for angle in [-MULTI_BALL_ANGLE, MULTI_BALL_ANGLE] {
+                        if count >= MAX_BALLS {
+                            break;
+                        }
+                        count += 1;
+                        commands.spawn((
+                            Ball,
+                            Position(position.0),
+                            Velocity(Vec2::from_angle(angle).rotate(velocity)),
+                            Collider::new(BALL_SIZE),
+                        ));
+                    }
+                }
+            }
+            PowerUp::ExtraLife => {
+                if let Ok(mut player) = player.single_mut() {
+                    player.lives = (player.lives + 1).min(PLAYER_MAX_LIVES);
+                }
+            }
+        }
+    }
+}
+
+/// Runs down the timed power-ups and ends the ones that ran out.
+pub fn tick_power_ups(mut active: ResMut<ActivePowerUps>, mut balls: Balls, time: Res<Time>) {
+    let mut slow_ended = false;
+    active.0.retain_mut(|(power_up, timer)| {
+        let finished = timer.tick(time.delta()).finished();
+        slow_ended |= finished && *power_up == PowerUp::SlowBall;
+        !finished
+    });
+
+    if slow_ended {
+        scale_ball_speed(&mut balls, 1.0 / SLOW_BALL_FACTOR);
+    }
+}
+
+/// Widens the paddle while the wide paddle power-up is active, keeping it
+/// centred where it is.
+pub fn resize_paddle(
+    active: Res<ActivePowerUps>,
+    mut player: Query<(&mut Position, &mut Collider), With<Player>>,
+    display_resolution: NonSend<DisplayResolution>,
+    game_status: Res<GameStatus>,
+) {
+    let Ok((mut position, mut collider)) = player.single_mut() else {
+        return;
+    };
+
+    let mut width = paddle_width(game_status.level);
+    if active.is_active(PowerUp::WidePaddle) {
+        width = (width as f32 * WIDE_PADDLE_FACTOR) as u32;
+    }
+    let size = Size::new(width, PLAYER_SIZE.height);
+    let old_width = collider.size().x;
+    if old_width == width as f32 {
+        return;
+    }
+
+    let right_edge = display_resolution.width as f32 - width as f32;
+    position.0.x = (position.0.x + (old_width - width as f32) / 2.0).clamp(0.0, right_edge);
+    *collider = Collider::new(size);
+}
+
+/// Holds balls that landed on the paddle while the catch power-up is active.
+#[allow(clippy::type_complexity)]
+pub fn catch_balls(
+    mut commands: Commands,
+    mut collisions: EventReader<Collision>,
+    active: Res<ActivePowerUps>,
+    mut balls: Query<(&Position, &mut Velocity), (With<Ball>, Without<Caught>)>,
+    player: Query<(Entity, &Position), With<Player>>,
+) {
+    if !active.is_active(PowerUp::Catch) {
+        return;
+    }
+    let Ok((paddle, paddle_position)) = player.single() else {
+        return;
+    };
+
+    let mut caught = Vec::new();
+    for collision in collisions.read() {
+        if collision.b != paddle || collision.normal != Vec2::NEG_Y || caught.contains(&collision.a)
+        {
+            continue;
+        }
+        let Ok((position, mut velocity)) = balls.get_mut(collision.a) else {
+            continue;
+        };
+
+        caught.push(collision.a);
+        commands.entity(collision.a).insert(Caught {
+            offset: position.0.x - paddle_position.0.x,
+            velocity: velocity.0,
+            timer: Timer::from_seconds(CATCH_HOLD_SECS, TimerMode::Once),
+        });
+        velocity.0 = Vec2::ZERO;
+    }
+}
+
+/// Moves caught balls along with the paddle and launches them once their time
+/// is up.
+#[allow(clippy::type_complexity)]
+pub fn carry_caught_balls(
+    mut commands: Commands,
+    balls: Query<(Entity, &mut Position, &mut Velocity, &mut Caught), With<Ball>>,
+    player: Query<(&Position, &Collider), (With<Player>, Without<Ball>)>,
+    time: Res<Time>,
+) {
+    let Ok((paddle_position, paddle_collider)) = player.single() else {
+        return;
+    };
+    let max_offset = paddle_collider.size().x - BALL_SIZE.width as f32;
+
+    for (entity, mut position, mut velocity, mut caught) in balls {
+        // The paddle shrinks back when wide paddle runs out.
+        caught.offset = caught.offset.clamp(0.0, max_offset.max(0.0));
+        position.0 = paddle_position.0 + Vec2::new(caught.offset, -(BALL_SIZE.height as f32));
+
+        if caught.timer.tick(time.delta()).finished() {
+            velocity.0 = caught.velocity;
+            commands.entity(entity).remove::<Caught>();
+        }
+    }
+}
+
+/// Ends all power-ups, e.g. when a new level starts.
+pub fn clear_power_ups(mut active: ResMut<ActivePowerUps>) {
+    active.0.clear();
+}
+
+/// Speeds all balls up or down by `factor`, including the launch velocity of
+/// caught ones.
+fn scale_ball_speed(balls: &mut Balls, factor: f32) {
+    for (_, mut velocity, caught) in balls.iter_mut() {
+        velocity.0 *= factor;
+        if let Some(mut caught) = caught {
+            caught.velocity *= factor;
+        }
+    }
+}
@@ -3,7 +3,7 @@ use core::fmt::Write;
 use embedded_graphics::{
     image::Image,
     mono_font::{
-        ascii::{FONT_5X8, FONT_6X10},
+        ascii::{FONT_4X6, FONT_5X8, FONT_6X10},
         MonoTextStyleBuilder,
     },
     pixelcolor::BinaryColor,
@@ -14,55 +14,72 @@ use embedded_graphics::{
 use heapless::String;
 
 use super::{
-    ball::{Ball, BALL_SIZE},
-    block::{Block, BLOCK_SIZE},
-    player::{Player, PLAYER_SIZE},
+    ball::{Ball, Serving, BALL_SIZE},
+    block::{Block, BrickKind, BLOCK_SIZE},
+    calibration::{CalibrationRun, CalibrationStep},
+    collision::Collider,
+    hal::GameDisplay,
+    level::LEVELS,
+    player::Player,
+    powerup::{ActivePowerUps, Capsule, CAPSULE_SIZE},
     resources::{
         DisplayResolution, DisplayResource, GameStatus, HEART_SPRITE_WIDTH, RAW_HEART_SPRITE,
         RAW_SPRITE_BEVY, SPRITE_BEVY_SIZE,
     },
+    state::{PauseMenu, PauseMenuItem},
     Position,
 };
 
-pub fn clear_screen(mut display_res: NonSendMut<DisplayResource>) {
+/// Dots of the aim indicator shown while serving, and pixels between them.
+const AIM_INDICATOR_DOTS: u32 = 5;
+const AIM_INDICATOR_SPACING: u32 = 3;
+
+/// How long before running out active power-ups start blinking in the HUD.
+const POWER_UP_BLINK_SECS: f32 = 2.0;
+
+/// Box on the calibration screen that shows where the stick points.
+const STICK_BOX_SIZE: Size = Size::new(21, 21);
+
+pub fn clear_screen<D: GameDisplay>(mut display_res: NonSendMut<DisplayResource<D>>) {
     let display = &mut display_res.display;
 
-    display.clear_buffer();
     display
         .clear(BinaryColor::Off)
         .expect("failed to clear display");
 }
 
-pub fn render_game(
-    mut display_res: NonSendMut<DisplayResource>,
-    blocks: Query<&Position, With<Block>>,
-    player: Query<&Position, With<Player>>,
+pub fn render_game<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    blocks: Query<(&Position, &Block)>,
+    player: Query<(&Position, &Collider), With<Player>>,
     balls: Query<&Position, With<Ball>>,
+    capsules: Query<(&Position, &Capsule)>,
+    serving: Query<(&Position, &Serving)>,
 ) {
     let display = &mut display_res.display;
 
-    for position in blocks {
-        let style = PrimitiveStyleBuilder::new()
-            .fill_color(BinaryColor::On)
-            .build();
-        let rect = embedded_graphics::primitives::Rectangle::new(position.0, BLOCK_SIZE);
+    for (position, block) in blocks {
+        let origin = position.pixel();
+        let pixels = (0..BLOCK_SIZE.height)
+            .flat_map(|y| (0..BLOCK_SIZE.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| brick_pixel(block.kind, x, y, BLOCK_SIZE))
+            .map(|(x, y)| Pixel(origin + Point::new(x as i32, y as i32), BinaryColor::On));
 
-        rect.into_styled(style)
-            .draw(display)
-            .expect("failed to draw block");
+        display.draw_iter(pixels).expect("failed to draw block");
     }
 
     let style = PrimitiveStyleBuilder::new()
         .fill_color(BinaryColor::On)
         .build();
 
-    if let Ok(player_position) = player.single() {
-        let rect = Rectangle::new(player_position.0, PLAYER_SIZE);
+    if let Ok((player_position, collider)) = player.single() {
+        let size = collider.size().as_uvec2();
+        let rect = Rectangle::new(player_position.pixel(), Size::new(size.x, size.y));
         rect.into_styled(style).draw(display).unwrap();
     }
 
     for position in balls {
-        let rect = embedded_graphics::primitives::Rectangle::new(position.0, BALL_SIZE);
+        let rect = embedded_graphics::primitives::Rectangle::new(position.pixel(), BALL_SIZE);
 
         let style = PrimitiveStyleBuilder::new()
             .fill_color(BinaryColor::On)
@@ -71,10 +88,70 @@ pub fn render_game(
         rect.into_styled(style).draw(display).unwrap();
     }
 
+    // Dotted line from the ball in the direction it will be served.
+    for (position, serving) in serving {
+        let center = position.0 + Vec2::new(BALL_SIZE.width as f32, BALL_SIZE.height as f32) / 2.0;
+        let dots = (1..=AIM_INDICATOR_DOTS).map(|dot| {
+            let point = (center + serving.direction() * (dot * AIM_INDICATOR_SPACING) as f32)
+                .round()
+                .as_ivec2();
+            Pixel(Point::new(point.x, point.y), BinaryColor::On)
+        });
+        display.draw_iter(dots).expect("failed to draw aim");
+    }
+
+    let outline = PrimitiveStyleBuilder::new()
+        .stroke_color(BinaryColor::On)
+        .stroke_width(1)
+        .build();
+    let letter_style = MonoTextStyleBuilder::new()
+        .font(&FONT_4X6)
+        .text_color(BinaryColor::On)
+        .build();
+
+    for (position, capsule) in capsules {
+        let origin = position.pixel();
+        Rectangle::new(origin, CAPSULE_SIZE)
+            .into_styled(outline)
+            .draw(display)
+            .unwrap();
+
+        let mut letter: String<1> = String::new();
+        letter.push(capsule.power_up.letter()).unwrap();
+        Text::with_baseline(
+            &letter,
+            origin + Point::new(2, 1),
+            letter_style,
+            Baseline::Top,
+        )
+        .draw(display)
+        .unwrap();
+    }
+
     display.flush().expect("failed to flush");
 }
 
-pub fn print_score(mut display_res: NonSendMut<DisplayResource>, game_status: ResMut<GameStatus>) {
+/// Whether pixel `x`, `y` of a brick of `size` is lit. Every kind has its own
+/// pattern, so they can be told apart on a 1-bit display.
+fn brick_pixel(kind: BrickKind, x: u32, y: u32, size: Size) -> bool {
+    let edge = x == 0 || y == 0 || x + 1 == size.width || y + 1 == size.height;
+    match kind {
+        // Outline
+        BrickKind::Weak => edge,
+        BrickKind::Normal => true,
+        // Outline hatched with vertical lines
+        BrickKind::Strong => edge || x & 1 == 0,
+        // Checkerboard dither
+        BrickKind::Indestructible => (x + y) & 1 == 0,
+        // Row of small crosses
+        BrickKind::Explosive => x % 4 == y % 4 || x % 4 == (4 - y % 4) % 4,
+    }
+}
+
+pub fn print_score<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    game_status: ResMut<GameStatus>,
+) {
     let display = &mut display_res.display;
 
     let mut score_text: String<16> = String::new();
@@ -90,10 +167,129 @@ pub fn print_score(mut display_res: NonSendMut<DisplayResource>, game_status: Re
         .unwrap();
 }
 
-pub fn print_lives(
+pub fn print_level<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    display_resolution: NonSendMut<DisplayResolution>,
+    game_status: Res<GameStatus>,
+) {
+    let display = &mut display_res.display;
+
+    let mut level_text: String<8> = String::new();
+    write!(level_text, "L{}", game_status.level + 1).unwrap();
+
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_5X8)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let text_width = level_text.len() as i32 * FONT_5X8.character_size.width as i32;
+    let x = (display_resolution.width as i32 - text_width) / 2;
+
+    Text::with_baseline(&level_text, Point::new(x, 0), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+}
+
+/// Letters of the active power-ups, left of the lives. They blink for the last
+/// [`POWER_UP_BLINK_SECS`] before running out.
+pub fn print_power_ups<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    display_resolution: NonSendMut<DisplayResolution>,
+    player: Query<&Player>,
+    power_ups: Res<ActivePowerUps>,
+) {
+    let display = &mut display_res.display;
+    let lives = player.single().map_or(0, |player| player.lives);
+
+    let mut letters: String<8> = String::new();
+    for (power_up, timer) in &power_ups.0 {
+        let remaining = timer.remaining_secs();
+        let blink_off = remaining < POWER_UP_BLINK_SECS && (remaining * 4.0) as u32 % 2 == 1;
+        letters
+            .push(if blink_off { ' ' } else { power_up.letter() })
+            .unwrap();
+    }
+
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_5X8)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let lives_x = display_resolution.width - HEART_SPRITE_WIDTH * (u32::from(lives) + 1);
+    let x = lives_x - letters.len() as u32 * FONT_5X8.character_size.width;
+
+    Text::with_baseline(&letters, Point::new(x as i32, 0), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+}
+
+/// "Get ready" above the paddle while the next ball is on its way.
+pub fn print_get_ready<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    display_resolution: NonSendMut<DisplayResolution>,
+) {
+    let display = &mut display_res.display;
+    let title = "Get ready";
+
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let text_width = title.len() as i32 * FONT_6X10.character_size.width as i32;
+    let x = (display_resolution.width as i32 - text_width) / 2;
+    let y = display_resolution.height as i32 - 20;
+
+    Text::with_baseline(title, Point::new(x, y), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+}
+
+pub fn display_level_intro<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    game_status: Res<GameStatus>,
+) {
+    let mut title: String<16> = String::new();
+    write!(title, "Level {}", game_status.level + 1).unwrap();
+
+    let display = &mut display_res.display;
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let text_width = title.len() as i32 * FONT_6X10.character_size.width as i32;
+    let text_height = FONT_6X10.character_size.height as i32;
+
+    let Size { width, height } = display.bounding_box().size;
+
+    // Calculate top-left position to center the text
+    let x = (width as i32 - text_width) / 2;
+    let y = (height as i32 - text_height) / 2;
+
+    Text::with_baseline(&title, Point::new(x, y), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+
+    let name = LEVELS[game_status.level].name;
+    let name_style = MonoTextStyleBuilder::new()
+        .font(&FONT_5X8)
+        .text_color(BinaryColor::On)
+        .build();
+    let name_width = name.len() as i32 * FONT_5X8.character_size.width as i32;
+    let x = (width as i32 - name_width) / 2;
+    let y = y + text_height + 2;
+
+    Text::with_baseline(name, Point::new(x, y), name_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    display.flush().expect("failed to flush display");
+}
+
+pub fn print_lives<D: GameDisplay>(
     display_resolution: NonSendMut<DisplayResolution>,
     mut player: Query<&mut Player, With<Player>>,
-    mut display_res: NonSendMut<DisplayResource>,
+    mut display_res: NonSendMut<DisplayResource<D>>,
 ) {
     let display = &mut display_res.display;
 
@@ -111,66 +307,206 @@ pub fn print_lives(
     }
 }
 
-pub fn display_game_over(
-    mut display_res: NonSendMut<DisplayResource>,
-    game_status: ResMut<GameStatus>,
+/// Box with the pause menu, drawn over the frozen last frame of the game.
+pub fn display_pause_menu<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    menu: Res<PauseMenu>,
 ) {
-    let mut title: String<20> = String::new();
-    write!(title, "You died! Score: {}", game_status.score).unwrap();
-
     let display = &mut display_res.display;
-    let text_style = MonoTextStyleBuilder::new()
+    let Size { width, height } = display.bounding_box().size;
+
+    let line_height = FONT_5X8.character_size.height as i32 + 1;
+    let box_size = Size::new(
+        60,
+        (FONT_6X10.character_size.height as i32
+            + 4
+            + PauseMenuItem::ALL.len() as i32 * line_height
+            + 4) as u32,
+    );
+    let top_left = Point::new(
+        (width - box_size.width) as i32 / 2,
+        (height - box_size.height) as i32 / 2,
+    );
+
+    let frame = PrimitiveStyleBuilder::new()
+        .fill_color(BinaryColor::Off)
+        .stroke_color(BinaryColor::On)
+        .stroke_width(1)
+        .build();
+    Rectangle::new(top_left, box_size)
+        .into_styled(frame)
+        .draw(display)
+        .unwrap();
+
+    let title = "Paused";
+    let title_style = MonoTextStyleBuilder::new()
         .font(&FONT_6X10)
         .text_color(BinaryColor::On)
         .build();
+    let title_width = title.len() as i32 * FONT_6X10.character_size.width as i32;
+    Text::with_baseline(
+        title,
+        Point::new((width as i32 - title_width) / 2, top_left.y + 2),
+        title_style,
+        Baseline::Top,
+    )
+    .draw(display)
+    .unwrap();
+
+    let item_style = MonoTextStyleBuilder::new()
+        .font(&FONT_5X8)
+        .text_color(BinaryColor::On)
+        .build();
+    let mut y = top_left.y + 2 + FONT_6X10.character_size.height as i32 + 2;
+    for item in PauseMenuItem::ALL {
+        let mut line: String<12> = String::new();
+        let marker = if item == menu.selected { '>' } else { ' ' };
+        write!(line, "{} {}", marker, item.label()).unwrap();
+
+        Text::with_baseline(
+            &line,
+            Point::new(top_left.x + 6, y),
+            item_style,
+            Baseline::Top,
+        )
+        .draw(display)
+        .unwrap();
+        y += line_height;
+    }
 
-    let text_width = title.len() as i32 * FONT_6X10.character_size.width as i32;
-    let text_height = FONT_6X10.character_size.height as i32;
+    display.flush().expect("failed to flush display");
+}
 
-    // Get display dimensions
-    let (width, height) = display.dimensions();
+/// Calibration screen: what to do next and where the stick points right now.
+pub fn display_calibration<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    run: Res<CalibrationRun>,
+) {
+    let display = &mut display_res.display;
+    let Size { width, .. } = display.bounding_box().size;
 
-    // Calculate top-left position to center the text
-    let x = (width as i32 - text_width) / 2;
-    let y = (height as i32 - text_height) / 2;
+    let title = if run.failed {
+        "Try again"
+    } else {
+        "Calibrate stick"
+    };
+    let title_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::On)
+        .build();
+    let title_width = title.len() as i32 * FONT_6X10.character_size.width as i32;
+    Text::with_baseline(
+        title,
+        Point::new((width as i32 - title_width) / 2, 0),
+        title_style,
+        Baseline::Top,
+    )
+    .draw(display)
+    .unwrap();
+
+    let prompt = match run.step {
+        CalibrationStep::Center => ["Leave the stick", "centred and press"],
+        CalibrationStep::Extremes => ["Move it to all ends", "then press again"],
+    };
+    let prompt_style = MonoTextStyleBuilder::new()
+        .font(&FONT_5X8)
+        .text_color(BinaryColor::On)
+        .build();
+    let mut y = FONT_6X10.character_size.height as i32 + 2;
+    for line in prompt {
+        let line_width = line.len() as i32 * FONT_5X8.character_size.width as i32;
+        Text::with_baseline(
+            line,
+            Point::new((width as i32 - line_width) / 2, y),
+            prompt_style,
+            Baseline::Top,
+        )
+        .draw(display)
+        .unwrap();
+        y += FONT_5X8.character_size.height as i32 + 1;
+    }
 
-    Text::with_baseline(&title, Point::new(x, y), text_style, Baseline::Top)
+    let top_left = Point::new((width - STICK_BOX_SIZE.width) as i32 / 2, y + 2);
+    let frame = PrimitiveStyleBuilder::new()
+        .stroke_color(BinaryColor::On)
+        .stroke_width(1)
+        .build();
+    Rectangle::new(top_left, STICK_BOX_SIZE)
+        .into_styled(frame)
         .draw(display)
         .unwrap();
+
+    // Raw readings across the box, higher paddle readings are further left.
+    let inside = STICK_BOX_SIZE.width as i32 - 4;
+    let scale = |reading: Option<u16>| {
+        reading.map_or(inside / 2, |reading| {
+            i32::from(reading.min(4095)) * inside / 4095
+        })
+    };
+    let dot = top_left
+        + Point::new(
+            1 + inside - scale(run.reading[0]),
+            1 + scale(run.reading[1]),
+        );
+    Rectangle::new(dot, Size::new(2, 2))
+        .into_styled(
+            PrimitiveStyleBuilder::new()
+                .fill_color(BinaryColor::On)
+                .build(),
+        )
+        .draw(display)
+        .unwrap();
+
     display.flush().expect("failed to flush display");
 }
 
-pub fn display_game_completed(
-    mut display_res: NonSendMut<DisplayResource>,
+pub fn display_game_over<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
     game_status: ResMut<GameStatus>,
 ) {
-    let mut title: String<20> = String::new();
-    write!(title, "You win! Score: {}", game_status.score).unwrap();
+    display_end_screen(&mut display_res.display, "You died!", game_status.score);
+}
+
+pub fn display_game_completed<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
+    game_status: ResMut<GameStatus>,
+) {
+    display_end_screen(&mut display_res.display, "You win!", game_status.score);
+}
+
+/// Centres `title` with the score on its own line below, where even a full
+/// `u32` fits across the screen.
+fn display_end_screen<D: GameDisplay>(display: &mut D, title: &str, score: u32) {
+    let mut score_text: String<17> = String::new();
+    write!(score_text, "Score: {score}").unwrap();
 
-    let display = &mut display_res.display;
     let text_style = MonoTextStyleBuilder::new()
         .font(&FONT_6X10)
         .text_color(BinaryColor::On)
         .build();
-
-    let text_width = title.len() as i32 * FONT_6X10.character_size.width as i32;
-    let text_height = FONT_6X10.character_size.height as i32;
+    let line_height = FONT_6X10.character_size.height as i32;
 
     // Get display dimensions
-    let (width, height) = display.dimensions();
-
-    // Calculate top-left position to center the text
-    let x = (width as i32 - text_width) / 2;
-    let y = (height as i32 - text_height) / 2;
-
-    Text::with_baseline(&title, Point::new(x, y), text_style, Baseline::Top)
+    let Size { width, height } = display.bounding_box().size;
+
+    let mut y = (height as i32 - 2 * line_height) / 2;
+    for line in [title, score_text.as_str()] {
+        let line_width = line.len() as i32 * FONT_6X10.character_size.width as i32;
+        Text::with_baseline(
+            line,
+            Point::new((width as i32 - line_width) / 2, y),
+            text_style,
+            Baseline::Top,
+        )
         .draw(display)
         .unwrap();
+        y += line_height;
+    }
     display.flush().expect("failed to flush display");
 }
 
-pub fn display_welcome(
-    mut display_res: NonSendMut<DisplayResource>,
+pub fn display_welcome<D: GameDisplay>(
+    mut display_res: NonSendMut<DisplayResource<D>>,
     display_resolution: NonSendMut<DisplayResolution>,
 ) {
     let display = &mut display_res.display;
@@ -1,48 +1,53 @@
+use alloc::boxed::Box;
 use bevy_ecs::resource::Resource;
 use embedded_graphics::pixelcolor::BinaryColor;
 use embedded_graphics::{image::ImageRaw, prelude::Size};
-use esp_hal::gpio::Input;
-use esp_hal::i2c::master::I2c;
-use esp_hal::rng::Rng;
-use ssd1306::{
-    mode::BufferedGraphicsMode, prelude::I2CInterface, size::DisplaySize128x64, Ssd1306,
-};
-
-pub type DisplayType<'a> = Ssd1306<
-    I2CInterface<I2c<'a, esp_hal::Blocking>>,
-    DisplaySize128x64,
-    BufferedGraphicsMode<DisplaySize128x64>,
->;
 
-// const VRX_PIN: u8 = 13;
-// const VRY_PIN: u8 = 14;
-// const BTN_PIN: u8 = 32;
+use super::hal::{
+    Button, GameDisplay, PaddleAxis, RandomSource, RotaryEncoder, SerialPort, SettingsStore,
+    TiltSensor,
+};
 
 const HEART_SPRITE: [u8; 8] = [0x00, 0x6e, 0xff, 0xef, 0x7e, 0x3c, 0x18, 0x00];
 pub const HEART_SPRITE_WIDTH: u32 = 8;
 pub const RAW_HEART_SPRITE: ImageRaw<'static, BinaryColor> =
     ImageRaw::<BinaryColor>::new(&HEART_SPRITE, HEART_SPRITE_WIDTH);
 
-type VrxPin = esp_hal::analog::adc::AdcPin<esp_hal::gpio::GpioPin<13>, esp_hal::peripherals::ADC2>;
-type VryPin = esp_hal::analog::adc::AdcPin<esp_hal::gpio::GpioPin<14>, esp_hal::peripherals::ADC2>;
+pub struct JoyStickResource {
+    /// VRY, steers the paddle.
+    pub axis: Box<dyn PaddleAxis>,
+    /// VRX, only calibrated so far. Boards without it leave it out.
+    pub cross_axis: Option<Box<dyn PaddleAxis>>,
+    pub btn: Box<dyn Button>,
+}
 
-#[derive(Resource)]
-pub struct JoyStickResource<'a> {
-    pub vrx_pin: VrxPin,
-    pub vry_pin: VryPin,
-    pub btn: Input<'a>,
+/// Rotary encoder read by [`EncoderInputPlugin`](super::EncoderInputPlugin).
+pub struct EncoderResource {
+    pub encoder: Box<dyn RotaryEncoder>,
+    /// The encoder's push switch.
+    pub btn: Box<dyn Button>,
 }
 
-pub type Adc<'a> = esp_hal::analog::adc::Adc<'a, esp_hal::peripherals::ADC2, esp_hal::Blocking>;
+/// Tilt sensor read by [`TiltInputPlugin`](super::TiltInputPlugin).
+pub struct TiltResource {
+    pub sensor: Box<dyn TiltSensor>,
+    pub btn: Box<dyn Button>,
+}
 
-#[derive(Resource)]
-pub struct AdcResource<'a> {
-    pub adc: Adc<'a>,
+/// Where [`SerialInputPlugin`](super::SerialInputPlugin) reads its commands
+/// from.
+pub struct SerialResource {
+    pub port: Box<dyn SerialPort>,
 }
 
-#[derive(Resource)]
-pub struct DisplayResource<'a> {
-    pub display: DisplayType<'a>,
+/// Where settings like the joystick calibration are kept. Without it the
+/// defaults are used and nothing is calibrated.
+pub struct SettingsStoreResource {
+    pub store: Box<dyn SettingsStore>,
+}
+
+pub struct DisplayResource<D: GameDisplay> {
+    pub display: D,
 }
 
 #[derive(Resource)]
@@ -51,25 +56,15 @@ pub struct DisplayResolution {
     pub height: u32,
 }
 
-#[derive(Resource)]
 pub struct RandResource {
-    pub rng: Rng,
-}
-
-#[derive(Default, PartialEq, Debug)]
-pub enum GameState {
-    #[default]
-    MainMenu,
-    Playing,
-    LevelCompleted,
-    GameOver,
-    Resetting,
+    pub rng: Box<dyn RandomSource>,
 }
 
 #[derive(Resource, Default)]
 pub struct GameStatus {
-    pub state: GameState,
     pub score: u32,
+    /// Index into `LEVELS` of the level being played.
+    pub level: usize,
 }
 
 // 'bevy', WxH Pixel = 48 x 42 px
@@ -0,0 +1,38 @@
+//! Blocking main loop for boards without an event loop of their own.
+
+use core::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::platform_support::time::Instant;
+use bevy::prelude::*;
+use embedded_hal::delay::DelayNs;
+
+/// Shortest time between two rendered frames. Slower frames (e.g. a long I2C
+/// flush) simply run more simulation ticks.
+pub const FRAME_BUDGET: Duration = Duration::from_millis(16);
+
+/// App runner that updates as fast as [`FRAME_BUDGET`] allows and sleeps on
+/// `delay` for the rest of each frame.
+///
+/// ```ignore
+/// app.set_runner(frame_limited(Delay::new())).run();
+/// ```
+pub fn frame_limited(mut delay: impl DelayNs + 'static) -> impl FnOnce(App) -> AppExit {
+    move |mut app| {
+        app.finish();
+        app.cleanup();
+        #[cfg(feature = "esp32")]
+        defmt::info!("running app");
+
+        loop {
+            let frame_start = Instant::now();
+            app.update();
+            if let Some(exit) = app.should_exit() {
+                return exit;
+            }
+            if let Some(remaining) = FRAME_BUDGET.checked_sub(frame_start.elapsed()) {
+                delay.delay_us(remaining.as_micros() as u32);
+            }
+        }
+    }
+}
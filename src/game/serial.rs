@@ -0,0 +1,94 @@
+//! Input backend driven by text commands over a serial port, e.g. from a PC or
+//! another microcontroller, or replayed from a recording.
+//!
+//! One command per line, lines that don't parse are ignored:
+//!
+//! | Command         | Action                                                  |
+//! |-----------------|---------------------------------------------------------|
+//! | `move <speed>`  | Moves the paddle at `speed`, from -1 (full left) to 1 (full right), until the next `move` |
+//! | `confirm`       | Starts a round, serves or picks the menu entry          |
+//! | `back`          | Leaves the menu                                         |
+//! | `pause`         | Pauses or resumes                                       |
+
+use bevy::prelude::*;
+use heapless::String;
+
+use super::{actions::InputActions, resources::SerialResource};
+
+/// Longest command line, longer ones are dropped.
+const MAX_LINE: usize = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Move(f32),
+    Confirm,
+    Back,
+    Pause,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let command = match words.next()? {
+            "move" => {
+                let speed = words.next()?.parse::<f32>().ok();
+                Command::Move(speed.filter(|speed| speed.is_finite())?.clamp(-1.0, 1.0))
+            }
+            "confirm" => Command::Confirm,
+            "back" => Command::Back,
+            "pause" => Command::Pause,
+            _ => return None,
+        };
+        words.next().is_none().then_some(command)
+    }
+}
+
+/// Command line being received and the paddle speed last asked for.
+#[derive(Resource, Default)]
+pub struct SerialInput {
+    line: String<MAX_LINE>,
+    /// The line got too long and is skipped up to its end.
+    overflowed: bool,
+    move_axis: f32,
+}
+
+impl SerialInput {
+    /// Adds a received byte, returning the command it completed.
+    fn push(&mut self, byte: u8) -> Option<Command> {
+        match byte {
+            b'\n' => {
+                let command = (!self.overflowed)
+                    .then(|| Command::parse(&self.line))
+                    .flatten();
+                self.line.clear();
+                self.overflowed = false;
+                command
+            }
+            b'\r' => None,
+            _ => {
+                if !byte.is_ascii() || self.line.push(char::from(byte)).is_err() {
+                    self.overflowed = true;
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Fills [`InputActions`] from the commands received since the last frame.
+pub fn read_serial(
+    mut serial: NonSendMut<SerialResource>,
+    mut input: ResMut<SerialInput>,
+    mut actions: ResMut<InputActions>,
+) {
+    while let Some(byte) = serial.port.read_byte() {
+        match input.push(byte) {
+            Some(Command::Move(speed)) => input.move_axis = speed,
+            Some(Command::Confirm) => actions.confirm = true,
+            Some(Command::Back) => actions.back = true,
+            Some(Command::Pause) => actions.pause = true,
+            None => {}
+        }
+    }
+    actions.move_axis = input.move_axis;
+}
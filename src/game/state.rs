@@ -1,60 +1,162 @@
 use bevy::prelude::*;
 
 use super::{
-    ball::Ball,
+    ball::{Ball, RespawnTimer},
     block::Block,
+    level::{LEVELS, LEVEL_INTRO_SECS},
     player::Player,
-    resources::{GameState, GameStatus},
+    powerup::Capsule,
+    resources::GameStatus,
 };
 
-#[derive(Debug, Event)]
-pub struct ResetGameEvent;
+/// Screen the game is on.
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    /// "Level N" screen shown before each level.
+    LevelIntro,
+    Playing,
+    /// Play is frozen behind the pause menu.
+    Paused,
+    GameOver,
+    /// All levels cleared.
+    CampaignCompleted,
+    /// Joystick calibration, on the first boot or with the button held down at
+    /// power-up.
+    Calibrating,
+}
+
+/// Entries of the pause menu, top to bottom.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum PauseMenuItem {
+    #[default]
+    Resume,
+    /// Starts over from the first level.
+    Restart,
+    /// Gives up the campaign and goes back to the title screen.
+    Quit,
+}
+
+impl PauseMenuItem {
+    pub const ALL: [PauseMenuItem; 3] = [
+        PauseMenuItem::Resume,
+        PauseMenuItem::Restart,
+        PauseMenuItem::Quit,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PauseMenuItem::Resume => "Resume",
+            PauseMenuItem::Restart => "Restart",
+            PauseMenuItem::Quit => "Quit",
+        }
+    }
+}
 
+/// Entry of the pause menu the stick points at.
+#[derive(Resource, Default)]
+pub struct PauseMenu {
+    pub selected: PauseMenuItem,
+}
+
+impl PauseMenu {
+    /// Moves the selection `by` entries, stopping at the first and last one.
+    pub fn select(&mut self, by: isize) {
+        let index = PauseMenuItem::ALL
+            .iter()
+            .position(|item| *item == self.selected)
+            .unwrap_or_default();
+        let index = index
+            .saturating_add_signed(by)
+            .min(PauseMenuItem::ALL.len() - 1);
+        self.selected = PauseMenuItem::ALL[index];
+    }
+}
+
+/// Counts down the "Level N" screen.
+#[derive(Resource)]
+pub struct LevelIntroTimer(pub Timer);
+
+/// Moves on to the next level once all blocks are gone, and ends the game when
+/// the last level is cleared or the last life is lost.
 pub fn update_game_state(
     player: Query<&Player, (With<Player>,)>,
     blocks: Query<&Block, (With<Block>,)>,
     mut game_status: ResMut<GameStatus>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    if blocks.is_empty() {
-        game_status.state = GameState::LevelCompleted;
+    // Several ticks can run before the state actually changes.
+    if matches!(*next_state, NextState::Pending(_)) {
         return;
     }
 
+    // Losing the last ball on the tick that clears the level still ends the
+    // game.
     let player = player.single().unwrap();
     if player.lives == 0 {
-        game_status.state = GameState::GameOver;
-        game_status.set_changed();
+        next_state.set(GameState::GameOver);
+        return;
+    }
+
+    // Indestructible blocks stay behind once the level is cleared.
+    if blocks.iter().all(|block| block.kind.is_indestructible()) {
+        if game_status.level + 1 < LEVELS.len() {
+            game_status.level += 1;
+            next_state.set(GameState::LevelIntro);
+        } else {
+            next_state.set(GameState::CampaignCompleted);
+        }
     }
 }
 
-pub fn reset_game(
-    mut commands: Commands,
-    mut events: EventReader<ResetGameEvent>,
-    ball_query: Query<Entity, With<Ball>>,
-    block_query: Query<Entity, With<Block>>,
-    player_query: Query<Entity, With<Player>>,
-    mut game_status: ResMut<GameStatus>,
+/// Opens the pause menu on its first entry.
+pub fn open_pause_menu(mut menu: ResMut<PauseMenu>) {
+    *menu = PauseMenu::default();
+}
+
+/// Starts over from the first level with no score.
+pub fn new_campaign(mut game_status: ResMut<GameStatus>) {
+    *game_status = GameStatus::default();
+}
+
+pub fn start_level_intro(mut commands: Commands) {
+    commands.insert_resource(LevelIntroTimer(Timer::from_seconds(
+        LEVEL_INTRO_SECS,
+        TimerMode::Once,
+    )));
+}
+
+pub fn tick_level_intro(
+    mut timer: ResMut<LevelIntroTimer>,
+    time: Res<Time>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    let Some(_) = events.read().next() else {
-        return;
-    };
+    if timer.0.tick(time.delta()).finished() {
+        next_state.set(GameState::Playing);
+    }
+}
 
-    // Despawn all existing game entities
-    ball_query
-        .iter()
-        .for_each(|entity| commands.entity(entity).despawn());
-    block_query
-        .iter()
-        .for_each(|entity| commands.entity(entity).despawn());
-    player_query
+/// Clears the balls, blocks and capsules left over from the previous level.
+#[allow(clippy::type_complexity)]
+pub fn despawn_level(
+    mut commands: Commands,
+    entities: Query<Entity, Or<(With<Ball>, With<Block>, With<Capsule>)>>,
+) {
+    // The new level starts with a ball right away.
+    commands.remove_resource::<RespawnTimer>();
+    entities
         .iter()
         .for_each(|entity| commands.entity(entity).despawn());
-
-    game_status.state = GameState::Resetting;
-    game_status.score = 0;
 }
 
-/// switch to playing mode
-pub fn switch_reset(mut game_status: ResMut<GameStatus>) {
-    game_status.state = GameState::Playing;
+/// Despawns everything of the campaign that just ended.
+#[allow(clippy::type_complexity)]
+pub fn despawn_round(
+    mut commands: Commands,
+    entities: Query<Entity, Or<(With<Ball>, With<Block>, With<Capsule>, With<Player>)>>,
+) {
+    entities
+        .iter()
+        .for_each(|entity| commands.entity(entity).despawn());
 }
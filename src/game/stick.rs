@@ -0,0 +1,219 @@
+//! Turns raw readings of the stick axis into how far it's pushed.
+//!
+//! Every joystick module rests at a slightly different ADC value and reaches
+//! different values at its ends, so the readings are first scaled with the
+//! module's [`JoystickCalibration`]. Small deflections around the centre are
+//! ignored, the rest is shaped by a [`ResponseCurve`].
+//!
+//! [`read_joystick`] is the input backend for the joystick module.
+
+use bevy::math::ops;
+use bevy::prelude::*;
+
+use super::{
+    actions::InputActions,
+    button::{ButtonLongPress, ButtonPressed},
+    player::PLAYER_SPEED,
+    resources::JoyStickResource,
+    state::GameState,
+};
+
+/// How strongly [`ResponseCurve::Exponential`] bends, higher is gentler near
+/// the centre.
+const EXPONENTIAL_STRENGTH: f32 = 3.0;
+
+/// Raw ADC values of an axis at rest and at both ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AxisCalibration {
+    pub center: u16,
+    /// Lowest reading, with the stick pushed fully right on the paddle axis.
+    pub low: u16,
+    /// Highest reading, with the stick pushed fully left on the paddle axis.
+    pub high: u16,
+}
+
+impl Default for AxisCalibration {
+    fn default() -> Self {
+        Self {
+            center: 2048,
+            low: 0,
+            high: 4095,
+        }
+    }
+}
+
+impl AxisCalibration {
+    /// Position of `raw` between the `high` end (-1) and the `low` end (1),
+    /// which is left to right on the paddle axis.
+    pub fn normalize(&self, raw: u16) -> f32 {
+        let (end, sign) = if raw > self.center {
+            (self.high, -1.0)
+        } else {
+            (self.low, 1.0)
+        };
+        let range = (f32::from(end) - f32::from(self.center)).abs();
+        if range == 0.0 {
+            return 0.0;
+        }
+
+        let distance = (f32::from(raw) - f32::from(self.center)).abs();
+        sign * (distance / range).min(1.0)
+    }
+
+    /// Smallest distance from the centre to either end.
+    pub fn travel(&self) -> u16 {
+        (self.center.saturating_sub(self.low)).min(self.high.saturating_sub(self.center))
+    }
+}
+
+/// Calibration of both axes of the joystick, kept in flash across reboots.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Default)]
+pub struct JoystickCalibration {
+    /// VRY, steers the paddle.
+    pub paddle: AxisCalibration,
+    /// VRX, the other axis.
+    pub cross: AxisCalibration,
+}
+
+impl JoystickCalibration {
+    /// Size of the stored calibration.
+    pub const BYTES: usize = 4 + 6 * 2 + 1;
+    /// Marks a stored calibration, erased flash reads as `0xff`.
+    const MAGIC: [u8; 4] = *b"JCA1";
+
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut bytes = [0; Self::BYTES];
+        bytes[..4].copy_from_slice(&Self::MAGIC);
+        for (chunk, value) in bytes[4..Self::BYTES - 1]
+            .chunks_exact_mut(2)
+            .zip(self.values())
+        {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[Self::BYTES - 1] = checksum(&bytes[..Self::BYTES - 1]);
+        bytes
+    }
+
+    /// Reads back a calibration written by [`JoystickCalibration::to_bytes`],
+    /// `None` if nothing valid was stored.
+    pub fn from_bytes(bytes: &[u8; Self::BYTES]) -> Option<Self> {
+        if bytes[..4] != Self::MAGIC
+            || bytes[Self::BYTES - 1] != checksum(&bytes[..Self::BYTES - 1])
+        {
+            return None;
+        }
+
+        let mut values = bytes[4..Self::BYTES - 1]
+            .chunks_exact(2)
+            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut axis = || AxisCalibration {
+            center: values.next().unwrap(),
+            low: values.next().unwrap(),
+            high: values.next().unwrap(),
+        };
+        Some(Self {
+            paddle: axis(),
+            cross: axis(),
+        })
+    }
+
+    fn values(&self) -> [u16; 6] {
+        let Self { paddle, cross } = self;
+        [
+            paddle.center,
+            paddle.low,
+            paddle.high,
+            cross.center,
+            cross.low,
+            cross.high,
+        ]
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// How deflection maps to paddle speed.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ResponseCurve {
+    /// Speed grows evenly with deflection.
+    #[default]
+    Linear,
+    /// Slow and precise near the centre, picking up quickly towards the ends.
+    Exponential,
+}
+
+impl ResponseCurve {
+    /// Shapes `input` in `0.0..=1.0`, keeping both ends in place.
+    fn apply(self, input: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => input,
+            ResponseCurve::Exponential => {
+                (ops::exp(EXPONENTIAL_STRENGTH * input) - 1.0)
+                    / (ops::exp(EXPONENTIAL_STRENGTH) - 1.0)
+            }
+        }
+    }
+}
+
+/// How the paddle responds to the stick.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct StickSettings {
+    /// Share of the travel around the centre that is ignored.
+    pub dead_zone: f32,
+    pub curve: ResponseCurve,
+    /// Paddle speed at full deflection, in pixels per second.
+    pub max_speed: f32,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.1,
+            curve: ResponseCurve::Linear,
+            max_speed: PLAYER_SPEED,
+        }
+    }
+}
+
+impl StickSettings {
+    /// Deflection of the axis with `calibration` for the reading `raw`, from -1
+    /// (full left) to 1 (full right). Zero inside the dead zone, which is taken
+    /// out of the range so the deflection still starts from zero at its edge.
+    pub fn deflection(&self, calibration: &AxisCalibration, raw: u16) -> f32 {
+        let position = calibration.normalize(raw);
+        let outside =
+            (position.abs() - self.dead_zone).max(0.0) / (1.0 - self.dead_zone).max(f32::EPSILON);
+        position.signum() * self.curve.apply(outside.min(1.0))
+    }
+}
+
+/// Fills [`InputActions`] from the joystick. The stick steers, pressing its
+/// button confirms and holding it down pauses.
+///
+/// A hold only pauses if its press left the state alone, so a press that
+/// already paused isn't undone by the hold that follows.
+#[allow(clippy::too_many_arguments)]
+pub fn read_joystick(
+    mut joystick: NonSendMut<JoyStickResource>,
+    settings: Res<StickSettings>,
+    calibration: Res<JoystickCalibration>,
+    state: Res<State<GameState>>,
+    mut pressed: EventReader<ButtonPressed>,
+    mut long_press: EventReader<ButtonLongPress>,
+    mut actions: ResMut<InputActions>,
+    mut pressed_in: Local<Option<GameState>>,
+) {
+    if let Some(adc_value) = joystick.axis.read() {
+        let deflection = settings.deflection(&calibration.paddle, adc_value);
+        actions.move_axis = deflection * settings.max_speed / PLAYER_SPEED;
+    }
+    if pressed.read().count() > 0 {
+        actions.confirm = true;
+        *pressed_in = Some(*state.get());
+    }
+    if long_press.read().count() > 0 && *pressed_in == Some(*state.get()) {
+        actions.pause = true;
+    }
+}
@@ -0,0 +1,179 @@
+//! Input backend that steers the paddle by tilting the controller, with an
+//! MPU6050 accelerometer inside.
+//!
+//! [`Mpu6050`] only needs an [`I2c`] bus, so it can share the display's bus
+//! and be tested against a mock. The roll angle is smoothed and measured from
+//! the level taken when zeroing, which happens with the first reading and
+//! whenever the button is held down. A short press confirms once the button is
+//! let go, so holding it down to zero doesn't also serve, pause or start a game.
+
+use bevy::math::ops;
+use bevy::prelude::*;
+use embedded_hal::i2c::I2c;
+
+use super::{
+    actions::InputActions,
+    button::{ButtonLongPress, ButtonReleased},
+    hal::TiltSensor,
+    resources::TiltResource,
+};
+
+/// Bus address with the AD0 pin low, it's `0x69` with AD0 high.
+pub const MPU6050_ADDRESS: u8 = 0x68;
+/// What the `WHO_AM_I` register reads on an MPU6050.
+const MPU6050_ID: u8 = 0x68;
+
+const REG_CONFIG: u8 = 0x1a;
+const REG_ACCEL_CONFIG: u8 = 0x1c;
+const REG_ACCEL_XOUT_H: u8 = 0x3b;
+const REG_PWR_MGMT_1: u8 = 0x6b;
+const REG_WHO_AM_I: u8 = 0x75;
+
+#[derive(Debug, PartialEq)]
+pub enum Mpu6050Error<E> {
+    I2c(E),
+    /// Something else answered, with this `WHO_AM_I`.
+    WrongDevice(u8),
+}
+
+impl<E> From<E> for Mpu6050Error<E> {
+    fn from(error: E) -> Self {
+        Mpu6050Error::I2c(error)
+    }
+}
+
+/// Driver for the accelerometer of an MPU6050, the gyroscope is left unused.
+pub struct Mpu6050<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C: I2c> Mpu6050<I2C> {
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self { i2c, address }
+    }
+
+    /// Checks that an MPU6050 answers and wakes it up, measuring up to 2 g with
+    /// the low pass filter at 44 Hz.
+    pub fn init(&mut self) -> Result<(), Mpu6050Error<I2C::Error>> {
+        let mut id = [0];
+        self.i2c
+            .write_read(self.address, &[REG_WHO_AM_I], &mut id)?;
+        if id[0] != MPU6050_ID {
+            return Err(Mpu6050Error::WrongDevice(id[0]));
+        }
+
+        // Out of sleep, clocked from the X gyro as the datasheet recommends.
+        self.i2c.write(self.address, &[REG_PWR_MGMT_1, 0x01])?;
+        self.i2c.write(self.address, &[REG_CONFIG, 0x03])?;
+        self.i2c.write(self.address, &[REG_ACCEL_CONFIG, 0x00])?;
+        Ok(())
+    }
+
+    /// Raw acceleration along x, y and z, 16384 counts per g.
+    pub fn acceleration(&mut self) -> Result<[i16; 3], I2C::Error> {
+        let mut bytes = [0; 6];
+        self.i2c
+            .write_read(self.address, &[REG_ACCEL_XOUT_H], &mut bytes)?;
+        Ok([
+            i16::from_be_bytes([bytes[0], bytes[1]]),
+            i16::from_be_bytes([bytes[2], bytes[3]]),
+            i16::from_be_bytes([bytes[4], bytes[5]]),
+        ])
+    }
+
+    /// Rotation around the x axis in radians, from gravity alone. Zero with the
+    /// chip lying flat, positive with the y axis pointing down.
+    pub fn roll(&mut self) -> Result<f32, I2C::Error> {
+        let [_, y, z] = self.acceleration()?;
+        Ok(ops::atan2(f32::from(y), f32::from(z)))
+    }
+
+    /// Gives back the bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C: I2c> TiltSensor for Mpu6050<I2C> {
+    fn read_roll(&mut self) -> Option<f32> {
+        self.roll().ok()
+    }
+}
+
+/// How tilting moves the paddle.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct TiltSettings {
+    /// Tilt from level for full paddle speed, in radians. Negative if the
+    /// sensor is mounted the other way round.
+    pub full_tilt: f32,
+    /// Share of [`TiltSettings::full_tilt`] around level that is ignored.
+    pub dead_zone: f32,
+    /// Time constant of the smoothing in seconds, zero for none. Readings that
+    /// jitter faster than that are evened out.
+    pub smoothing_secs: f32,
+}
+
+impl Default for TiltSettings {
+    fn default() -> Self {
+        Self {
+            full_tilt: core::f32::consts::FRAC_PI_6,
+            dead_zone: 0.1,
+            smoothing_secs: 0.1,
+        }
+    }
+}
+
+/// Smoothed roll and the roll taken as level.
+#[derive(Resource, Default)]
+pub struct TiltInput {
+    pub roll: Option<f32>,
+    pub level: f32,
+    /// The button is held down past a long press, letting it go won't confirm.
+    zeroing: bool,
+}
+
+/// Fills [`InputActions`] from the tilt sensor and its button.
+pub fn read_tilt(
+    mut tilt: NonSendMut<TiltResource>,
+    settings: Res<TiltSettings>,
+    mut input: ResMut<TiltInput>,
+    time: Res<Time>,
+    mut released: EventReader<ButtonReleased>,
+    mut long_press: EventReader<ButtonLongPress>,
+    mut actions: ResMut<InputActions>,
+) {
+    let zero = long_press.read().count() > 0;
+    input.zeroing |= zero;
+    if released.read().count() > 0 {
+        actions.confirm |= !input.zeroing;
+        input.zeroing = false;
+    }
+
+    if let Some(reading) = tilt.sensor.read_roll() {
+        let roll = match input.roll {
+            Some(roll) if settings.smoothing_secs > 0.0 => {
+                let share = 1.0 - ops::exp(-time.delta_secs() / settings.smoothing_secs);
+                roll + (reading - roll) * share
+            }
+            Some(_) => reading,
+            None => {
+                input.level = reading;
+                reading
+            }
+        };
+        input.roll = Some(roll);
+    }
+
+    let Some(roll) = input.roll else {
+        return;
+    };
+    if zero {
+        input.level = roll;
+    }
+
+    let position = (roll - input.level) / settings.full_tilt;
+    let outside = (position.abs() - settings.dead_zone).max(0.0)
+        / (1.0 - settings.dead_zone).max(f32::EPSILON);
+    actions.move_axis = position.signum() * outside.min(1.0);
+}
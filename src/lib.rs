@@ -1,9 +1,13 @@
 #![no_std]
 
+extern crate alloc;
+
+#[cfg(feature = "esp32")]
 use esp_hal::time::{Duration, Instant};
 
 pub mod game;
 
+#[cfg(feature = "esp32")]
 pub fn blocking_delay(duration: Duration) {
     let delay_start = Instant::now();
     while delay_start.elapsed() < duration {}
@@ -0,0 +1,598 @@
+//! Headless harness that runs the game on the host with scripted input.
+
+#![allow(dead_code)]
+
+use core::convert::Infallible;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+
+use esp32_breakout_bevy::game::{
+    ball::{Ball, Serving, BALL_SIZE},
+    block::{Block, BrickKind, BLOCK_SIZE},
+    collision::{Collider, Collision, Wall},
+    hal::{
+        Button, GameDisplay, PaddleAxis, RandomSource, RotaryEncoder, SerialPort, SettingsStore,
+        TiltSensor,
+    },
+    player::Player,
+    powerup::{ActivePowerUps, Capsule, CapsuleDrops, PowerUp},
+    resources::{
+        DisplayResolution, DisplayResource, EncoderResource, GameStatus, JoyStickResource,
+        RandResource, SerialResource, SettingsStoreResource, TiltResource,
+    },
+    state::GameState,
+    BreakoutInputPlugin, BreakoutPlugin, BreakoutRenderPlugin, EncoderInputPlugin, Position,
+    SerialInputPlugin, TiltInputPlugin, Velocity,
+};
+
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = 64;
+/// Time that passes between two updates unless changed with `Harness::set_frame_time`.
+pub const FRAME_TIME: Duration = Duration::from_millis(50);
+
+/// Joystick state for a single frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameInput {
+    /// Raw ADC value of the VRY axis.
+    pub axis: u16,
+    /// Raw ADC value of the VRX axis.
+    pub cross_axis: u16,
+    /// Counts the rotary encoder turned during the frame.
+    pub turn: i32,
+    /// Roll of the tilt controller in radians.
+    pub tilt: f32,
+    pub button: bool,
+}
+
+impl FrameInput {
+    pub const IDLE: Self = Self {
+        axis: 2048,
+        cross_axis: 2048,
+        turn: 0,
+        tilt: 0.0,
+        button: false,
+    };
+    pub const LEFT: Self = Self {
+        axis: 4095,
+        ..Self::IDLE
+    };
+    pub const RIGHT: Self = Self {
+        axis: 0,
+        ..Self::IDLE
+    };
+    pub const PRESS: Self = Self {
+        button: true,
+        ..Self::IDLE
+    };
+}
+
+#[derive(Default)]
+struct Script {
+    queued: VecDeque<FrameInput>,
+    current: Option<FrameInput>,
+}
+
+impl Script {
+    fn current(&self) -> FrameInput {
+        self.current.unwrap_or(FrameInput::IDLE)
+    }
+}
+
+struct ScriptedAxis(Rc<RefCell<Script>>);
+
+impl PaddleAxis for ScriptedAxis {
+    fn read(&mut self) -> Option<u16> {
+        Some(self.0.borrow().current().axis)
+    }
+}
+
+struct ScriptedCrossAxis(Rc<RefCell<Script>>);
+
+impl PaddleAxis for ScriptedCrossAxis {
+    fn read(&mut self) -> Option<u16> {
+        Some(self.0.borrow().current().cross_axis)
+    }
+}
+
+struct ScriptedEncoder(Rc<RefCell<Script>>);
+
+impl RotaryEncoder for ScriptedEncoder {
+    fn take_counts(&mut self) -> i32 {
+        self.0.borrow().current().turn
+    }
+}
+
+struct ScriptedTilt(Rc<RefCell<Script>>);
+
+impl TiltSensor for ScriptedTilt {
+    fn read_roll(&mut self) -> Option<f32> {
+        Some(self.0.borrow().current().tilt)
+    }
+}
+
+struct ScriptedButton(Rc<RefCell<Script>>);
+
+impl Button for ScriptedButton {
+    fn is_pressed(&mut self) -> bool {
+        self.0.borrow().current().button
+    }
+}
+
+/// Serial port receiving the bytes queued with [`Harness::send`].
+struct ScriptedSerial(Rc<RefCell<VecDeque<u8>>>);
+
+impl SerialPort for ScriptedSerial {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.0.borrow_mut().pop_front()
+    }
+}
+
+/// Deterministic xorshift generator.
+pub struct SeededRng(pub u32);
+
+impl RandomSource for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+}
+
+/// Settings store kept in memory, cloned handles share the same contents so a
+/// test can reboot with what the last run saved.
+#[derive(Clone, Default)]
+pub struct MemoryStore(Rc<RefCell<Option<Vec<u8>>>>);
+
+impl MemoryStore {
+    /// What was saved last, `None` if nothing was.
+    pub fn saved(&self) -> Option<Vec<u8>> {
+        self.0.borrow().clone()
+    }
+}
+
+impl SettingsStore for MemoryStore {
+    fn load(&mut self, bytes: &mut [u8]) -> bool {
+        match &*self.0.borrow() {
+            Some(saved) if saved.len() >= bytes.len() => {
+                bytes.copy_from_slice(&saved[..bytes.len()]);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn save(&mut self, bytes: &[u8]) -> bool {
+        *self.0.borrow_mut() = Some(bytes.to_vec());
+        true
+    }
+}
+
+/// In-memory 128x64 display.
+pub struct FrameBuffer {
+    pixels: [bool; (WIDTH * HEIGHT) as usize],
+    pub flushes: usize,
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        Self {
+            pixels: [false; (WIDTH * HEIGHT) as usize],
+            flushes: 0,
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        self.pixels[(y * WIDTH + x) as usize]
+    }
+
+    pub fn lit_pixels(&self) -> usize {
+        self.pixels.iter().filter(|on| **on).count()
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH, HEIGHT)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if (0..WIDTH as i32).contains(&point.x) && (0..HEIGHT as i32).contains(&point.y) {
+                self.pixels[(point.y as u32 * WIDTH + point.x as u32) as usize] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GameDisplay for FrameBuffer {
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+/// Asserts that two positions or velocities are equal up to float rounding.
+#[track_caller]
+pub fn assert_near(actual: Vec2, expected: Vec2) {
+    assert!(
+        actual.abs_diff_eq(expected, 1e-3),
+        "{actual} is not close to {expected}"
+    );
+}
+
+/// The game `App` built from the same plugins as the firmware, stepped one frame
+/// at a time.
+pub struct Harness {
+    pub app: App,
+    script: Rc<RefCell<Script>>,
+    serial: Rc<RefCell<VecDeque<u8>>>,
+}
+
+/// Input backend the harness drives.
+enum Controller {
+    /// The joystick, with its calibration in the store if there is one.
+    Joystick(Option<MemoryStore>),
+    /// The rotary encoder, with the scripted button as its switch.
+    Encoder,
+    /// The tilt controller, with the scripted button on it.
+    Tilt,
+    /// Commands over the serial port.
+    Serial,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Self::with_seed(0x1234_5678)
+    }
+
+    pub fn with_seed(seed: u32) -> Self {
+        Self::build(seed, Controller::Joystick(None))
+    }
+
+    /// Boots with the calibration in `store`, or on the calibration screen if
+    /// there is none. Nothing happens until the first [`Harness::step`], so the
+    /// script decides whether the button is held at power-up.
+    pub fn with_store(store: MemoryStore) -> Self {
+        Self::build(0x1234_5678, Controller::Joystick(Some(store)))
+    }
+
+    /// Plays with the rotary encoder instead of the joystick, the script turns
+    /// it and presses its switch.
+    pub fn with_encoder() -> Self {
+        Self::build(0x1234_5678, Controller::Encoder)
+    }
+
+    /// Plays by tilting instead of with the joystick, the script tilts the
+    /// controller and presses its button.
+    pub fn with_tilt() -> Self {
+        Self::build(0x1234_5678, Controller::Tilt)
+    }
+
+    /// Plays with commands sent with [`Harness::send`] instead of the joystick.
+    pub fn with_serial() -> Self {
+        Self::build(0x1234_5678, Controller::Serial)
+    }
+
+    fn build(seed: u32, controller: Controller) -> Self {
+        let script = Rc::new(RefCell::new(Script::default()));
+        let serial = Rc::new(RefCell::new(VecDeque::new()));
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            BreakoutPlugin,
+            BreakoutRenderPlugin::<FrameBuffer>::default(),
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME_TIME))
+        .insert_non_send_resource(DisplayResource {
+            display: FrameBuffer::new(),
+        })
+        .insert_non_send_resource(DisplayResolution {
+            width: WIDTH,
+            height: HEIGHT,
+        })
+        .insert_non_send_resource(RandResource {
+            rng: Box::new(SeededRng(seed)),
+        });
+
+        match controller {
+            Controller::Joystick(store) => {
+                app.add_plugins(BreakoutInputPlugin)
+                    .insert_non_send_resource(JoyStickResource {
+                        axis: Box::new(ScriptedAxis(script.clone())),
+                        cross_axis: Some(Box::new(ScriptedCrossAxis(script.clone()))),
+                        btn: Box::new(ScriptedButton(script.clone())),
+                    });
+                if let Some(store) = store {
+                    app.insert_non_send_resource(SettingsStoreResource {
+                        store: Box::new(store),
+                    });
+                }
+            }
+            Controller::Encoder => {
+                app.add_plugins(EncoderInputPlugin)
+                    .insert_non_send_resource(EncoderResource {
+                        encoder: Box::new(ScriptedEncoder(script.clone())),
+                        btn: Box::new(ScriptedButton(script.clone())),
+                    });
+            }
+            Controller::Tilt => {
+                app.add_plugins(TiltInputPlugin)
+                    .insert_non_send_resource(TiltResource {
+                        sensor: Box::new(ScriptedTilt(script.clone())),
+                        btn: Box::new(ScriptedButton(script.clone())),
+                    });
+            }
+            Controller::Serial => {
+                app.add_plugins(SerialInputPlugin)
+                    .insert_non_send_resource(SerialResource {
+                        port: Box::new(ScriptedSerial(serial.clone())),
+                    });
+            }
+        }
+        app.finish();
+        app.cleanup();
+
+        Self {
+            app,
+            script,
+            serial,
+        }
+    }
+
+    /// Changes how much time passes between two updates.
+    pub fn set_frame_time(&mut self, frame_time: Duration) -> &mut Self {
+        self.app
+            .insert_resource(TimeUpdateStrategy::ManualDuration(frame_time));
+        self
+    }
+
+    /// Queues inputs that are consumed one per frame by [`Harness::step`].
+    pub fn script(&mut self, inputs: impl IntoIterator<Item = FrameInput>) -> &mut Self {
+        self.script.borrow_mut().queued.extend(inputs);
+        self
+    }
+
+    /// Queues `commands` on the serial port, they arrive with the next frame.
+    pub fn send(&mut self, commands: &str) -> &mut Self {
+        self.serial.borrow_mut().extend(commands.bytes());
+        self
+    }
+
+    /// Runs `frames` updates. Once the script runs out the stick is idle.
+    pub fn step(&mut self, frames: usize) -> &mut Self {
+        for _ in 0..frames {
+            {
+                let mut script = self.script.borrow_mut();
+                script.current = script.queued.pop_front();
+            }
+            self.app.update();
+        }
+        self
+    }
+
+    /// Runs until every queued input has been consumed.
+    pub fn run_script(&mut self) -> &mut Self {
+        let frames = self.script.borrow().queued.len();
+        self.step(frames)
+    }
+
+    /// Presses the button on the title screen and waits until the round started.
+    pub fn start(&mut self) -> &mut Self {
+        self.script([FrameInput::PRESS]).step(1).finish_intro()
+    }
+
+    /// Presses the button to launch the ball waiting on the paddle. The ball
+    /// leaves on the frame after the press.
+    pub fn serve(&mut self) -> &mut Self {
+        self.script([FrameInput::PRESS, FrameInput::IDLE])
+            .run_script()
+    }
+
+    /// Aim of the ball waiting on the paddle, if there is one.
+    pub fn serve_angle(&mut self) -> Option<f32> {
+        let mut query = self.world_mut().query::<&Serving>();
+        query
+            .single(self.world_mut())
+            .ok()
+            .map(|serving| serving.angle)
+    }
+
+    /// Steps through the "Level N" screen until the level is being played.
+    pub fn finish_intro(&mut self) -> &mut Self {
+        for _ in 0..100 {
+            if self.state() == GameState::Playing {
+                return self;
+            }
+            self.step(1);
+        }
+        panic!("level intro did not finish, game is in {:?}", self.state());
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+
+    pub fn state(&self) -> GameState {
+        **self.app.world().resource::<State<GameState>>()
+    }
+
+    pub fn status(&self) -> &GameStatus {
+        self.app.world().resource::<GameStatus>()
+    }
+
+    pub fn display(&self) -> &FrameBuffer {
+        &self
+            .app
+            .world()
+            .non_send_resource::<DisplayResource<FrameBuffer>>()
+            .display
+    }
+
+    pub fn lives(&mut self) -> Option<u8> {
+        let mut query = self.world_mut().query::<&Player>();
+        query
+            .single(self.world_mut())
+            .ok()
+            .map(|player| player.lives)
+    }
+
+    pub fn set_lives(&mut self, lives: u8) {
+        let mut query = self.world_mut().query::<&mut Player>();
+        query.single_mut(self.world_mut()).unwrap().lives = lives;
+    }
+
+    pub fn paddle(&mut self) -> Option<Entity> {
+        let mut query = self.world_mut().query_filtered::<Entity, With<Player>>();
+        query.single(self.world_mut()).ok()
+    }
+
+    pub fn paddle_position(&mut self) -> Option<Vec2> {
+        let mut query = self.world_mut().query_filtered::<&Position, With<Player>>();
+        query
+            .single(self.world_mut())
+            .ok()
+            .map(|position| position.0)
+    }
+
+    pub fn block_count(&mut self) -> usize {
+        let mut query = self.world_mut().query::<&Block>();
+        query.iter(self.world_mut()).count()
+    }
+
+    pub fn blocks(&mut self) -> Vec<(Entity, Vec2, u8)> {
+        let mut query = self.world_mut().query::<(Entity, &Position, &Block)>();
+        query
+            .iter(self.world_mut())
+            .map(|(entity, position, block)| (entity, position.0, block.lives))
+            .collect()
+    }
+
+    pub fn balls(&mut self) -> Vec<(Entity, Vec2)> {
+        let mut query = self
+            .world_mut()
+            .query_filtered::<(Entity, &Position), With<Ball>>();
+        query
+            .iter(self.world_mut())
+            .map(|(entity, position)| (entity, position.0))
+            .collect()
+    }
+
+    pub fn ball_positions(&mut self) -> Vec<Vec2> {
+        self.balls()
+            .into_iter()
+            .map(|(_, position)| position)
+            .collect()
+    }
+
+    pub fn ball_velocity(&mut self, ball: Entity) -> Vec2 {
+        self.world_mut().get::<Velocity>(ball).unwrap().0
+    }
+
+    /// Adds another ball in play.
+    pub fn spawn_ball(&mut self, position: Vec2, velocity: Vec2) -> Entity {
+        self.world_mut()
+            .spawn((
+                Ball,
+                Position(position),
+                Velocity(velocity),
+                Collider::new(BALL_SIZE),
+            ))
+            .id()
+    }
+
+    /// Moves `ball` and overrides its velocity, serving it if it was still on
+    /// the paddle.
+    pub fn place_ball(&mut self, ball: Entity, position: Vec2, velocity: Vec2) {
+        let mut entity = self.world_mut().entity_mut(ball);
+        entity.remove::<Serving>();
+        entity.get_mut::<Position>().unwrap().0 = position;
+        entity.get_mut::<Velocity>().unwrap().0 = velocity;
+    }
+
+    pub fn walls(&mut self) -> Vec<Entity> {
+        let mut query = self.world_mut().query_filtered::<Entity, With<Wall>>();
+        query.iter(self.world_mut()).collect()
+    }
+
+    /// Collisions reported during the last frame.
+    pub fn collisions(&self) -> Vec<Collision> {
+        self.app
+            .world()
+            .resource::<Events<Collision>>()
+            .iter_current_update_events()
+            .copied()
+            .collect()
+    }
+
+    pub fn spawn_block(&mut self, position: Vec2, kind: BrickKind) -> Entity {
+        self.world_mut()
+            .spawn((
+                Block::new(kind),
+                Position(position),
+                Collider::new(BLOCK_SIZE),
+            ))
+            .id()
+    }
+
+    pub fn despawn_blocks(&mut self) {
+        let blocks: Vec<Entity> = self
+            .blocks()
+            .into_iter()
+            .map(|(entity, ..)| entity)
+            .collect();
+        for entity in blocks {
+            self.world_mut().despawn(entity);
+        }
+    }
+
+    pub fn paddle_width(&mut self) -> f32 {
+        let mut query = self.world_mut().query_filtered::<&Collider, With<Player>>();
+        query.single(self.world_mut()).unwrap().size().x
+    }
+
+    /// Makes one in `one_in` destroyed blocks drop a capsule, zero for none.
+    pub fn set_capsule_drops(&mut self, one_in: u32) {
+        self.world_mut().insert_resource(CapsuleDrops { one_in });
+    }
+
+    pub fn spawn_capsule(&mut self, position: Vec2, power_up: PowerUp) -> Entity {
+        self.world_mut()
+            .spawn((
+                Capsule { power_up },
+                Position(position),
+                Velocity(Vec2::new(0.0, 20.0)),
+            ))
+            .id()
+    }
+
+    pub fn capsules(&mut self) -> Vec<(Vec2, PowerUp)> {
+        let mut query = self.world_mut().query::<(&Position, &Capsule)>();
+        query
+            .iter(self.world_mut())
+            .map(|(position, capsule)| (position.0, capsule.power_up))
+            .collect()
+    }
+
+    pub fn power_ups(&self) -> &ActivePowerUps {
+        self.app.world().resource::<ActivePowerUps>()
+    }
+}
@@ -0,0 +1,1787 @@
+//! Gameplay regression tests that run on the host through the headless harness.
+//!
+//! Run them with `cargo host-test`.
+
+mod common;
+
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_6};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::{assert_near, FrameInput, Harness, MemoryStore, FRAME_TIME, HEIGHT, WIDTH};
+use esp32_breakout_bevy::game::ball::{
+    BallCollisions, AIM_SPEED, BALL_SPEED, MAX_LAUNCH_ANGLE, RESPAWN_SECS, SERVE_TIMEOUT_SECS,
+};
+use esp32_breakout_bevy::game::block::{BrickKind, BLOCK_SIZE};
+use esp32_breakout_bevy::game::broadphase::Broadphase;
+use esp32_breakout_bevy::game::button::{ButtonGesture, ButtonTimings, ButtonTracker};
+use esp32_breakout_bevy::game::calibration::{CalibrationRun, CalibrationStep, MIN_TRAVEL};
+use esp32_breakout_bevy::game::collision::Collision;
+use esp32_breakout_bevy::game::encoder::EncoderSettings;
+use esp32_breakout_bevy::game::level::LEVELS;
+use esp32_breakout_bevy::game::player::PLAYER_SPEED;
+use esp32_breakout_bevy::game::powerup::{PowerUp, CATCH_HOLD_SECS, POWER_UP_SECS};
+use esp32_breakout_bevy::game::resources::GameStatus;
+use esp32_breakout_bevy::game::serial::Command;
+use esp32_breakout_bevy::game::state::GameState;
+use esp32_breakout_bevy::game::stick::{
+    AxisCalibration, JoystickCalibration, ResponseCurve, StickSettings,
+};
+use esp32_breakout_bevy::game::tilt::TiltSettings;
+use esp32_breakout_bevy::game::GameSet;
+
+#[test]
+fn starts_on_title_screen() {
+    let mut harness = Harness::new();
+    harness.step(1);
+
+    assert_eq!(harness.state(), GameState::MainMenu);
+    assert_eq!(harness.lives(), None);
+    assert!(harness.display().flushes > 0);
+    assert!(harness.display().lit_pixels() > 0);
+}
+
+#[test]
+fn button_starts_round() {
+    let mut harness = Harness::new();
+    harness.start();
+
+    assert_eq!(harness.state(), GameState::Playing);
+    assert_eq!(harness.status().score, 0);
+    assert_eq!(harness.lives(), Some(3));
+    assert_eq!(harness.block_count(), 30);
+    assert_eq!(harness.balls().len(), 1);
+}
+
+#[test]
+fn new_ball_waits_on_paddle_until_served() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, position) = harness.balls()[0];
+    let paddle = harness.paddle_position().unwrap();
+
+    assert_eq!(position, paddle + Vec2::new(18.0, -4.0));
+    harness.step(5);
+    assert_eq!(harness.ball_velocity(ball), Vec2::ZERO);
+
+    // The ball moves along with the paddle.
+    harness.script([FrameInput::RIGHT; 2]).run_script();
+    let paddle = harness.paddle_position().unwrap();
+    assert_eq!(harness.balls()[0].1, paddle + Vec2::new(18.0, -4.0));
+
+    harness.serve();
+    assert!(harness.serve_angle().is_none());
+    assert!(harness.ball_velocity(ball).y < 0.0);
+}
+
+#[test]
+fn new_ball_is_aimed_upwards_at_any_angle() {
+    let mut angles = Vec::new();
+    for seed in 1..20 {
+        let mut harness = Harness::with_seed(seed);
+        harness.start().serve();
+        let (ball, _) = harness.balls()[0];
+        let velocity = harness.ball_velocity(ball);
+
+        assert!((velocity.length() - BALL_SPEED).abs() < 1e-3);
+        assert!(velocity.y < 0.0);
+        assert!(velocity.x.abs() <= -velocity.y + 1e-3);
+        angles.push(velocity.x);
+    }
+
+    angles.sort_by(f32::total_cmp);
+    angles.dedup();
+    assert!(angles.len() > 10);
+}
+
+#[test]
+fn stick_aims_the_serve() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+    let angle = harness.serve_angle().unwrap();
+
+    harness.script([FrameInput::LEFT; 2]).run_script();
+    let aimed = harness.serve_angle().unwrap();
+    assert!((aimed - (angle - AIM_SPEED * 0.1).max(-MAX_LAUNCH_ANGLE)).abs() < 1e-3);
+
+    // Turning stops at the largest launch angle.
+    harness.script([FrameInput::RIGHT; 30]).run_script();
+    assert_eq!(harness.serve_angle(), Some(MAX_LAUNCH_ANGLE));
+
+    harness.serve();
+    let velocity = harness.ball_velocity(ball);
+    assert!((velocity.x - velocity.y.abs()).abs() < 1e-3);
+    assert!(velocity.x > 0.0);
+}
+
+#[test]
+fn aim_is_shown_above_the_ball() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.script([FrameInput::LEFT; 30]).run_script();
+    let (_, position) = harness.balls()[0];
+
+    // Last dot, aimed at 45 degrees to the left from the centre of the ball.
+    let center = position + Vec2::splat(2.0);
+    let dot = (center + Vec2::new(-15.0, -15.0) / 2f32.sqrt()).round();
+    assert!(harness.display().pixel(dot.x as u32, dot.y as u32));
+
+    harness.serve().step(1);
+    assert!(!harness.display().pixel(dot.x as u32, dot.y as u32));
+}
+
+#[test]
+fn unserved_ball_launches_on_its_own() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+
+    harness.step((SERVE_TIMEOUT_SECS / 0.05) as usize - 1);
+    assert_eq!(harness.ball_velocity(ball), Vec2::ZERO);
+
+    harness.step(2);
+    assert!(harness.serve_angle().is_none());
+    assert!(harness.ball_velocity(ball).y < 0.0);
+}
+
+#[test]
+fn paddle_follows_stick_and_stays_on_screen() {
+    let mut harness = Harness::new();
+    harness.start().serve();
+    let start = harness.paddle_position().unwrap();
+
+    harness.script([FrameInput::LEFT; 3]).run_script();
+    assert!((harness.paddle_position().unwrap().x - (start.x - 15.0)).abs() < 1e-3);
+
+    harness.script([FrameInput::RIGHT; 30]).run_script();
+    assert_eq!(harness.paddle_position().unwrap().x, WIDTH as f32 - 40.0);
+
+    harness.script([FrameInput::LEFT; 30]).run_script();
+    assert_eq!(harness.paddle_position().unwrap().x, 0.0);
+    assert_eq!(harness.paddle_position().unwrap().y, start.y);
+}
+
+/// How far the paddle moves in one frame with the raw stick reading `axis`.
+fn paddle_step(settings: StickSettings, axis: u16) -> f32 {
+    calibrated_paddle_step(settings, JoystickCalibration::default(), axis)
+}
+
+fn calibrated_paddle_step(
+    settings: StickSettings,
+    calibration: JoystickCalibration,
+    axis: u16,
+) -> f32 {
+    let mut harness = Harness::new();
+    harness.world_mut().insert_resource(settings);
+    harness.world_mut().insert_resource(calibration);
+    harness.start().serve();
+    let start = harness.paddle_position().unwrap().x;
+    harness
+        .script([FrameInput {
+            axis,
+            ..FrameInput::IDLE
+        }])
+        .run_script();
+    harness.paddle_position().unwrap().x - start
+}
+
+#[test]
+fn paddle_speed_follows_stick_deflection() {
+    let settings = StickSettings {
+        dead_zone: 0.0,
+        ..default()
+    };
+
+    // 100 px/s at full deflection, 50 ms per frame.
+    assert!((paddle_step(settings, 0) - 5.0).abs() < 1e-3);
+    assert!((paddle_step(settings, 1024) - 2.5).abs() < 1e-3);
+    assert!((paddle_step(settings, 2048 + 1024) + 2.5).abs() < 0.01);
+    assert_eq!(paddle_step(settings, 2048), 0.0);
+
+    let faster = StickSettings {
+        max_speed: 200.0,
+        ..settings
+    };
+    assert!((paddle_step(faster, 1024) - 5.0).abs() < 1e-3);
+}
+
+#[test]
+fn stick_dead_zone_is_ignored() {
+    let settings = StickSettings {
+        dead_zone: 0.2,
+        ..default()
+    };
+
+    assert_eq!(paddle_step(settings, 2048 - 400), 0.0);
+    assert_eq!(paddle_step(settings, 2048 + 400), 0.0);
+    // The speed starts from zero at the edge of the dead zone.
+    assert!((paddle_step(settings, 1024) - 5.0 * 0.3 / 0.8).abs() < 1e-3);
+    assert!((paddle_step(settings, 0) - 5.0).abs() < 1e-3);
+}
+
+#[test]
+fn exponential_curve_is_gentle_near_centre() {
+    let linear = StickSettings {
+        dead_zone: 0.0,
+        ..default()
+    };
+    let exponential = StickSettings {
+        curve: ResponseCurve::Exponential,
+        ..linear
+    };
+
+    assert!(paddle_step(exponential, 1024) < paddle_step(linear, 1024) / 2.0);
+    assert!(paddle_step(exponential, 1024) > 0.0);
+    assert!((paddle_step(exponential, 0) - 5.0).abs() < 1e-3);
+}
+
+#[test]
+fn stick_is_scaled_by_its_calibration() {
+    let settings = StickSettings {
+        dead_zone: 0.0,
+        ..default()
+    };
+    let calibration = JoystickCalibration {
+        paddle: AxisCalibration {
+            center: 1800,
+            low: 300,
+            high: 3900,
+        },
+        ..default()
+    };
+    let step = |axis| calibrated_paddle_step(settings, calibration, axis);
+
+    assert_eq!(step(1800), 0.0);
+    assert!((step(300) - 5.0).abs() < 1e-3);
+    assert!((step(1050) - 2.5).abs() < 1e-3);
+    // Readings past the ends don't go any faster.
+    assert!((step(0) - 5.0).abs() < 1e-3);
+    assert!((step(2850) + 2.5).abs() < 1e-3);
+}
+
+/// Stick at the raw readings `axis` and `cross_axis`, button up.
+fn stick(axis: u16, cross_axis: u16) -> FrameInput {
+    FrameInput {
+        axis,
+        cross_axis,
+        ..FrameInput::IDLE
+    }
+}
+
+/// Takes the calibration screen through both steps, the centre at
+/// (`center`, `center`) and `ends` visited in order.
+fn calibrate(harness: &mut Harness, center: u16, ends: &[(u16, u16)]) {
+    let press = FrameInput {
+        button: true,
+        ..stick(center, center)
+    };
+    harness.script([press, stick(center, center)]);
+    harness.script(
+        ends.iter()
+            .map(|&(axis, cross_axis)| stick(axis, cross_axis)),
+    );
+    harness.script([press, FrameInput::IDLE]).run_script();
+}
+
+fn calibration(harness: &Harness) -> JoystickCalibration {
+    *harness.app.world().resource::<JoystickCalibration>()
+}
+
+#[test]
+fn first_boot_opens_calibration() {
+    let mut harness = Harness::with_store(MemoryStore::default());
+    harness.step(1);
+
+    assert_eq!(harness.state(), GameState::Calibrating);
+    assert!(harness.display().lit_pixels() > 0);
+
+    // The button doesn't start a round from here.
+    harness
+        .script([FrameInput::PRESS, FrameInput::IDLE])
+        .run_script();
+    assert_eq!(harness.state(), GameState::Calibrating);
+}
+
+#[test]
+fn calibration_is_saved_and_used_after_reboot() {
+    let store = MemoryStore::default();
+    let mut harness = Harness::with_store(store.clone());
+    harness.step(1);
+    calibrate(
+        &mut harness,
+        1800,
+        &[(300, 1800), (3900, 1800), (1800, 200), (1800, 3500)],
+    );
+
+    let expected = JoystickCalibration {
+        paddle: AxisCalibration {
+            center: 1800,
+            low: 300,
+            high: 3900,
+        },
+        cross: AxisCalibration {
+            center: 1800,
+            low: 200,
+            high: 3500,
+        },
+    };
+    assert_eq!(harness.state(), GameState::MainMenu);
+    assert_eq!(calibration(&harness), expected);
+    assert!(store.saved().is_some());
+
+    let mut rebooted = Harness::with_store(store);
+    rebooted.step(1);
+    assert_eq!(rebooted.state(), GameState::MainMenu);
+    assert_eq!(calibration(&rebooted), expected);
+
+    // The calibrated end of the stick is full speed.
+    rebooted.start().serve();
+    let start = rebooted.paddle_position().unwrap().x;
+    rebooted.script([stick(300, 1800)]).run_script();
+    assert_near(
+        rebooted.paddle_position().unwrap(),
+        Vec2::new(start + 5.0, rebooted.paddle_position().unwrap().y),
+    );
+}
+
+#[test]
+fn holding_the_button_at_power_up_recalibrates() {
+    let store = MemoryStore::default();
+    let mut harness = Harness::with_store(store.clone());
+    harness.step(1);
+    calibrate(&mut harness, 2048, &[(0, 0), (4095, 4095)]);
+    let saved = store.saved();
+
+    let mut rebooted = Harness::with_store(store.clone());
+    rebooted.script([FrameInput::PRESS; 5]).run_script();
+    assert_eq!(rebooted.state(), GameState::Calibrating);
+    // Still held from power-up, so the centre isn't taken yet.
+    assert_eq!(
+        rebooted.app.world().resource::<CalibrationRun>().step,
+        CalibrationStep::Center
+    );
+
+    rebooted.step(1);
+    calibrate(&mut rebooted, 2000, &[(100, 100), (4000, 4000)]);
+    assert_eq!(rebooted.state(), GameState::MainMenu);
+    assert_eq!(calibration(&rebooted).paddle.center, 2000);
+    assert_ne!(store.saved(), saved);
+}
+
+#[test]
+fn calibration_with_too_little_travel_is_repeated() {
+    let store = MemoryStore::default();
+    let mut harness = Harness::with_store(store.clone());
+    harness.step(1);
+    calibrate(
+        &mut harness,
+        2048,
+        &[(2048 - MIN_TRAVEL + 50, 2048), (4095, 0)],
+    );
+
+    assert_eq!(harness.state(), GameState::Calibrating);
+    let run = harness.app.world().resource::<CalibrationRun>();
+    assert!(run.failed);
+    assert_eq!(run.step, CalibrationStep::Center);
+    assert_eq!(store.saved(), None);
+}
+
+/// Gestures seen while feeding a tracker the pin levels at the given
+/// milliseconds.
+fn gestures(timings: ButtonTimings, timeline: &[(u64, bool)]) -> Vec<ButtonGesture> {
+    let mut tracker = ButtonTracker::default();
+    timeline
+        .iter()
+        .flat_map(|&(millis, pressed)| {
+            tracker.update(pressed, Duration::from_millis(millis), &timings)
+        })
+        .collect()
+}
+
+#[test]
+fn button_bounce_is_filtered() {
+    let timeline = [
+        (0, true),
+        (2, false),
+        (4, true),
+        (7, false),
+        (9, true),
+        (30, true),
+        (100, false),
+        (103, true),
+        (106, false),
+        (200, false),
+    ];
+
+    assert_eq!(
+        gestures(ButtonTimings::default(), &timeline),
+        [ButtonGesture::Pressed, ButtonGesture::Released]
+    );
+}
+
+#[test]
+fn holding_the_button_is_one_long_press() {
+    let timeline = [
+        (0, true),
+        (500, true),
+        (800, true),
+        (900, true),
+        (1000, false),
+    ];
+
+    assert_eq!(
+        gestures(ButtonTimings::default(), &timeline),
+        [
+            ButtonGesture::Pressed,
+            ButtonGesture::LongPress,
+            ButtonGesture::Released
+        ]
+    );
+}
+
+#[test]
+fn two_quick_presses_are_a_double_press() {
+    let timeline = [
+        (0, true),
+        (100, false),
+        (200, true),
+        (250, false),
+        // A third press starts over.
+        (400, true),
+        (450, false),
+        // Too late for a double press.
+        (800, true),
+    ];
+
+    assert_eq!(
+        gestures(ButtonTimings::default(), &timeline),
+        [
+            ButtonGesture::Pressed,
+            ButtonGesture::Released,
+            ButtonGesture::Pressed,
+            ButtonGesture::DoublePress,
+            ButtonGesture::Released,
+            ButtonGesture::Pressed,
+            ButtonGesture::Released,
+            ButtonGesture::Pressed,
+        ]
+    );
+}
+
+#[test]
+fn button_timings_are_configurable() {
+    let timings = ButtonTimings {
+        debounce: Duration::from_millis(50),
+        long_press: Duration::from_millis(200),
+        double_press: Duration::from_millis(100),
+    };
+    let timeline = [
+        (0, true),
+        (40, false),
+        (60, false),
+        (150, true),
+        (350, true),
+    ];
+
+    assert_eq!(
+        gestures(timings, &timeline),
+        [
+            ButtonGesture::Pressed,
+            ButtonGesture::Released,
+            ButtonGesture::Pressed,
+            ButtonGesture::LongPress,
+        ]
+    );
+}
+
+#[test]
+fn bouncing_button_pauses_only_once() {
+    let mut harness = Harness::new();
+    harness.start().serve();
+    harness.set_frame_time(Duration::from_millis(5));
+    harness.script([FrameInput::IDLE; 5]).run_script();
+
+    harness
+        .script([
+            FrameInput::PRESS,
+            FrameInput::IDLE,
+            FrameInput::PRESS,
+            FrameInput::IDLE,
+            FrameInput::IDLE,
+        ])
+        .run_script()
+        .step(1);
+
+    assert_eq!(harness.state(), GameState::Paused);
+}
+
+#[test]
+fn movement_follows_elapsed_time() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_frame_time(Duration::from_millis(25));
+    let (ball, _) = harness.balls()[0];
+    harness.place_ball(ball, Vec2::new(60.0, 40.0), Vec2::new(20.0, 20.0));
+    let paddle = harness.paddle_position().unwrap();
+
+    // Half the frame time moves half as far per frame.
+    harness.script([FrameInput::LEFT; 4]).run_script();
+
+    assert_near(harness.ball_positions()[0], Vec2::new(62.0, 42.0));
+    assert_near(
+        harness.paddle_position().unwrap(),
+        paddle - Vec2::new(10.0, 0.0),
+    );
+}
+
+#[test]
+fn ball_speed_does_not_depend_on_frame_rate() {
+    let travelled = |frame_time: Duration, frames: usize| {
+        let mut harness = Harness::new();
+        harness.start();
+        harness.set_frame_time(frame_time);
+        let (ball, _) = harness.balls()[0];
+        harness.place_ball(ball, Vec2::new(20.0, 40.0), Vec2::new(20.0, 0.0));
+        harness.step(frames);
+        harness.ball_positions()[0].x - 20.0
+    };
+
+    // One second of play, drawn at 100, 20 and 4 frames per second.
+    assert!((travelled(Duration::from_millis(10), 100) - 20.0).abs() < 1e-3);
+    assert!((travelled(Duration::from_millis(50), 20) - 20.0).abs() < 1e-3);
+    assert!((travelled(Duration::from_millis(250), 4) - 20.0).abs() < 1e-3);
+}
+
+#[test]
+fn ball_bounces_off_walls_and_ceiling() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+
+    harness.place_ball(ball, Vec2::new(0.0, 40.0), Vec2::new(-20.0, 20.0));
+    harness.step(1);
+    assert_eq!(harness.ball_velocity(ball), Vec2::new(20.0, 20.0));
+    assert!(harness.ball_positions()[0].x >= 0.0);
+
+    harness.place_ball(
+        ball,
+        Vec2::new(WIDTH as f32 - 4.0, 40.0),
+        Vec2::new(20.0, 20.0),
+    );
+    harness.step(1);
+    assert_eq!(harness.ball_velocity(ball), Vec2::new(-20.0, 20.0));
+    assert!(harness.ball_positions()[0].x <= WIDTH as f32 - 4.0);
+
+    // Clear the ball's way to the ceiling. The other blocks keep the level
+    // going, without any it would be won on the first tick.
+    let in_the_way: Vec<_> = harness
+        .blocks()
+        .into_iter()
+        .filter(|(_, position, _)| position.x < 64.0 && position.x + BLOCK_SIZE.width as f32 > 60.0)
+        .collect();
+    for (block, ..) in in_the_way {
+        harness.world_mut().despawn(block);
+    }
+    harness.place_ball(ball, Vec2::new(60.0, 0.0), Vec2::new(0.0, -20.0));
+    harness.step(1);
+    assert_eq!(harness.ball_velocity(ball), Vec2::new(0.0, 20.0));
+}
+
+#[test]
+fn ball_bounces_off_paddle() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+    let paddle = harness.paddle_position().unwrap();
+
+    harness.place_ball(ball, paddle + Vec2::new(18.0, -4.0), Vec2::new(0.0, 20.0));
+    harness.step(1);
+
+    assert_near(harness.ball_velocity(ball), Vec2::new(0.0, -20.0));
+    assert!(harness.ball_positions()[0].y + 4.0 <= paddle.y);
+    assert_eq!(harness.lives(), Some(3));
+}
+
+#[test]
+fn bounces_are_reported_as_collisions() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+    let walls = harness.walls();
+    assert_eq!(walls.len(), 3);
+
+    harness.place_ball(ball, Vec2::new(0.0, 40.0), Vec2::new(-20.0, 20.0));
+    harness.step(1);
+    let collisions = harness.collisions();
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].a, ball);
+    assert!(walls.contains(&collisions[0].b));
+    assert_eq!(collisions[0].normal, Vec2::X);
+    assert_eq!(collisions[0].depth, 0.0);
+
+    let paddle = harness.paddle().unwrap();
+    let paddle_position = harness.paddle_position().unwrap();
+    harness.place_ball(
+        ball,
+        paddle_position + Vec2::new(18.0, -4.0),
+        Vec2::new(0.0, 20.0),
+    );
+    harness.step(1);
+    let collisions = harness.collisions();
+    assert_eq!(collisions.len(), 1);
+    assert_eq!((collisions[0].a, collisions[0].b), (ball, paddle));
+    assert_eq!(collisions[0].normal, Vec2::NEG_Y);
+}
+
+#[test]
+fn ball_inside_paddle_is_pushed_out() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+    let paddle = harness.paddle_position().unwrap();
+
+    harness.place_ball(ball, paddle + Vec2::new(18.0, -3.0), Vec2::new(0.0, 20.0));
+    harness.step(1);
+
+    let collisions = harness.collisions();
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].normal, Vec2::NEG_Y);
+    assert_near(Vec2::splat(collisions[0].depth), Vec2::splat(1.0));
+    assert_near(harness.ball_velocity(ball), Vec2::new(0.0, -20.0));
+    assert!(harness.ball_positions()[0].y + 4.0 <= paddle.y);
+}
+
+#[derive(Resource, Default)]
+struct Telemetry {
+    collisions: usize,
+}
+
+#[test]
+fn extra_systems_can_hook_into_game_sets() {
+    let mut harness = Harness::new();
+    harness.app.init_resource::<Telemetry>().add_systems(
+        FixedUpdate,
+        (|mut collisions: EventReader<Collision>, mut telemetry: ResMut<Telemetry>| {
+            telemetry.collisions += collisions.read().count();
+        })
+        .after(GameSet::Collision)
+        .before(GameSet::Cleanup),
+    );
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+
+    harness.place_ball(ball, Vec2::new(0.0, 40.0), Vec2::new(-20.0, 20.0));
+    harness.step(1);
+
+    assert_eq!(harness.app.world().resource::<Telemetry>().collisions, 1);
+}
+
+/// Drops the ball onto the paddle so that its centre lands `offset` pixels
+/// right of the paddle centre, and returns the velocity it bounces off with.
+fn bounce_off_paddle(offset: f32, input: FrameInput) -> Vec2 {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+    let paddle = harness.paddle_position().unwrap();
+
+    // Already touching the paddle, so it bounces on the first tick.
+    let position = paddle + Vec2::new(18.0 + offset, -3.9);
+    harness.place_ball(ball, position, Vec2::new(0.0, 30.0));
+    harness.script([input]).step(1);
+    harness.ball_velocity(ball)
+}
+
+#[test]
+fn paddle_centre_bounces_straight_up() {
+    let velocity = bounce_off_paddle(0.0, FrameInput::IDLE);
+
+    assert_near(velocity, Vec2::new(0.0, -30.0));
+}
+
+#[test]
+fn paddle_edges_bounce_at_steep_angles() {
+    let left = bounce_off_paddle(-20.0, FrameInput::IDLE);
+    let right = bounce_off_paddle(20.0, FrameInput::IDLE);
+    let halfway = bounce_off_paddle(10.0, FrameInput::IDLE);
+
+    // 60 degrees from vertical at the edges, 30 degrees halfway out
+    assert_near(left, Vec2::new(-30.0 * 0.866_025, -30.0 * 0.5));
+    assert_near(right, Vec2::new(30.0 * 0.866_025, -30.0 * 0.5));
+    assert_near(halfway, Vec2::new(30.0 * 0.5, -30.0 * 0.866_025));
+}
+
+#[test]
+fn moving_paddle_adds_english() {
+    let still = bounce_off_paddle(0.0, FrameInput::IDLE);
+    let moving_right = bounce_off_paddle(0.0, FrameInput::RIGHT);
+    let moving_left = bounce_off_paddle(0.0, FrameInput::LEFT);
+
+    assert!(moving_right.x > still.x + 5.0);
+    assert!(moving_left.x < still.x - 5.0);
+    assert!((moving_right.length() - 30.0).abs() < 1e-3);
+    assert!(moving_right.y < 0.0 && moving_left.y < 0.0);
+}
+
+#[test]
+fn block_takes_two_hits() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+    let (block, block_position, lives) = harness
+        .blocks()
+        .into_iter()
+        .max_by(|(_, a, _), (_, b, _)| a.y.total_cmp(&b.y))
+        .unwrap();
+    assert_eq!(lives, 2);
+
+    let below_block = block_position + Vec2::new(8.0, 3.0);
+    harness.place_ball(ball, below_block, Vec2::new(0.0, -20.0));
+    harness.step(1);
+
+    let hit = harness
+        .blocks()
+        .into_iter()
+        .find(|(entity, ..)| *entity == block);
+    assert_eq!(hit.map(|(.., lives)| lives), Some(1));
+    assert_eq!(harness.ball_velocity(ball), Vec2::new(0.0, 20.0));
+    assert_eq!(harness.status().score, 0);
+
+    harness.place_ball(ball, below_block, Vec2::new(0.0, -20.0));
+    harness.step(1);
+
+    assert!(harness.blocks().iter().all(|(entity, ..)| *entity != block));
+    assert_eq!(harness.block_count(), 29);
+    assert_eq!(harness.status().score, 10);
+}
+
+#[test]
+fn ball_flies_through_destroyed_block() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+    let row = bottom_row(&mut harness);
+    let (block, block_position, _) = row[2];
+    let (above, ..) = harness
+        .blocks()
+        .into_iter()
+        .find(|(_, position, _)| *position == block_position - Vec2::new(0.0, 4.0))
+        .unwrap();
+
+    let below_block = block_position + Vec2::new(8.0, 3.0);
+    for _ in 0..2 {
+        harness.place_ball(ball, below_block, Vec2::new(0.0, -20.0));
+        harness.step(1);
+    }
+    assert!(harness.blocks().iter().all(|(entity, ..)| *entity != block));
+
+    harness.place_ball(ball, below_block, Vec2::new(0.0, -100.0));
+    harness.step(1);
+    let hits: Vec<_> = harness.collisions().iter().map(|hit| hit.b).collect();
+    assert_eq!(hits, [above]);
+}
+
+#[test]
+fn ball_bounces_off_block_spawned_mid_round() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+
+    let block = harness.spawn_block(Vec2::new(60.0, 40.0), BrickKind::Normal);
+    harness.place_ball(ball, Vec2::new(68.0, 43.0), Vec2::new(0.0, -20.0));
+    harness.step(1);
+
+    assert_eq!(harness.ball_velocity(ball), Vec2::new(0.0, 20.0));
+    let hit = harness
+        .blocks()
+        .into_iter()
+        .find(|(entity, ..)| *entity == block);
+    assert_eq!(hit.map(|(.., lives)| lives), Some(1));
+}
+
+#[test]
+fn only_static_colliders_rebuild_the_grid() {
+    let mut harness = Harness::new();
+    harness.start().step(1);
+    let last_changed = |harness: &mut Harness| {
+        harness
+            .world_mut()
+            .resource_ref::<Broadphase>()
+            .last_changed()
+    };
+
+    let built = last_changed(&mut harness);
+    let (ball, _) = harness.balls()[0];
+    harness.world_mut().despawn(ball);
+    harness.step(2);
+    assert_eq!(last_changed(&mut harness), built);
+
+    let (block, ..) = harness.blocks()[0];
+    harness.world_mut().despawn(block);
+    harness.step(1);
+    assert_ne!(last_changed(&mut harness), built);
+}
+
+/// Level of a few blocks in a row with the ball parked out of the way.
+fn clear_level(harness: &mut Harness, kinds: &[BrickKind]) -> Vec<Entity> {
+    harness.despawn_blocks();
+    let (ball, _) = harness.balls()[0];
+    harness.place_ball(ball, Vec2::new(120.0, 50.0), Vec2::ZERO);
+    kinds
+        .iter()
+        .enumerate()
+        .map(|(index, kind)| harness.spawn_block(Vec2::new(2.0 + index as f32 * 21.0, 20.0), *kind))
+        .collect()
+}
+
+#[test]
+fn strong_and_weak_bricks_take_their_hit_points() {
+    let mut harness = Harness::new();
+    harness.start();
+    let blocks = clear_level(&mut harness, &[BrickKind::Weak, BrickKind::Strong]);
+    let (ball, _) = harness.balls()[0];
+
+    let mut hits = 0;
+    while harness.block_count() == 2 {
+        harness.place_ball(ball, Vec2::new(10.0, 23.0), Vec2::new(0.0, -20.0));
+        harness.step(1);
+        hits += 1;
+    }
+    assert_eq!(hits, 1);
+    assert_eq!(harness.status().score, 5);
+
+    hits = 0;
+    while harness.block_count() == 1 {
+        harness.place_ball(ball, Vec2::new(31.0, 23.0), Vec2::new(0.0, -20.0));
+        harness.step(1);
+        hits += 1;
+    }
+    assert_eq!(hits, 3);
+    assert_eq!(harness.status().score, 25);
+    assert!(harness
+        .blocks()
+        .iter()
+        .all(|(entity, ..)| !blocks.contains(entity)));
+}
+
+#[test]
+fn indestructible_bricks_survive_and_are_not_needed_to_clear_level() {
+    let mut harness = Harness::new();
+    harness.start();
+    let blocks = clear_level(&mut harness, &[BrickKind::Indestructible, BrickKind::Weak]);
+    let (ball, _) = harness.balls()[0];
+
+    for _ in 0..5 {
+        harness.place_ball(ball, Vec2::new(10.0, 23.0), Vec2::new(0.0, -20.0));
+        harness.step(1);
+        assert_eq!(harness.ball_velocity(ball), Vec2::new(0.0, 20.0));
+    }
+    assert_eq!(harness.block_count(), 2);
+    assert_eq!(harness.state(), GameState::Playing);
+
+    harness.place_ball(ball, Vec2::new(31.0, 23.0), Vec2::new(0.0, -20.0));
+    harness.step(2);
+    assert_eq!(harness.state(), GameState::LevelIntro);
+    assert_eq!(harness.status().level, 1);
+    assert_eq!(harness.status().score, 5);
+    assert!(harness
+        .blocks()
+        .iter()
+        .all(|(entity, ..)| *entity != blocks[0]));
+}
+
+#[test]
+fn explosive_bricks_damage_neighbours_in_a_chain() {
+    let mut harness = Harness::new();
+    harness.start();
+    let blocks = clear_level(
+        &mut harness,
+        &[
+            BrickKind::Explosive,
+            BrickKind::Explosive,
+            BrickKind::Normal,
+            BrickKind::Normal,
+        ],
+    );
+    let (ball, _) = harness.balls()[0];
+
+    harness.place_ball(ball, Vec2::new(10.0, 23.0), Vec2::new(0.0, -20.0));
+    harness.step(1);
+
+    // Both explosives are gone, the first normal brick is damaged by the second
+    // blast and the one after it is out of reach.
+    let lives: Vec<_> = harness
+        .blocks()
+        .into_iter()
+        .map(|(entity, _, lives)| (blocks.iter().position(|block| *block == entity), lives))
+        .collect();
+    assert_eq!(lives.len(), 2);
+    assert!(lives.contains(&(Some(2), 1)));
+    assert!(lives.contains(&(Some(3), 2)));
+    assert_eq!(harness.status().score, 30);
+}
+
+#[test]
+fn brick_kinds_look_different() {
+    let kinds = [
+        BrickKind::Weak,
+        BrickKind::Normal,
+        BrickKind::Strong,
+        BrickKind::Indestructible,
+        BrickKind::Explosive,
+    ];
+    let mut harness = Harness::new();
+    harness.start();
+    clear_level(&mut harness, &kinds);
+    harness.step(1);
+
+    let looks: Vec<Vec<bool>> = (0..kinds.len())
+        .map(|index| {
+            let left = 2 + index as u32 * 21;
+            (20..23)
+                .flat_map(|y| (left..left + 20).map(move |x| (x, y)))
+                .map(|(x, y)| harness.display().pixel(x, y))
+                .collect()
+        })
+        .collect();
+    for (index, look) in looks.iter().enumerate() {
+        assert!(look.contains(&true));
+        assert!(
+            !looks[index + 1..].contains(look),
+            "{:?} looks like another kind",
+            kinds[index]
+        );
+    }
+}
+
+/// Frames until a power-up collected now runs out.
+const POWER_UP_FRAMES: usize = (POWER_UP_SECS / 0.05) as usize + 1;
+
+/// Drops a capsule right above the paddle and waits until it's collected.
+fn collect(harness: &mut Harness, power_up: PowerUp) {
+    harness.spawn_capsule(Vec2::new(60.0, 50.0), power_up);
+    harness.step(2);
+    assert!(
+        harness.capsules().is_empty(),
+        "{power_up:?} was not collected"
+    );
+}
+
+#[test]
+fn destroyed_blocks_drop_capsules_that_fall_off_screen() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_capsule_drops(1);
+    clear_level(&mut harness, &[BrickKind::Weak, BrickKind::Weak]);
+    let (ball, _) = harness.balls()[0];
+
+    harness.place_ball(ball, Vec2::new(10.0, 23.0), Vec2::new(0.0, -20.0));
+    harness.step(1);
+    let capsules = harness.capsules();
+    assert_eq!(capsules.len(), 1);
+    assert_eq!(capsules[0].0.x, 8.0);
+    let start_y = capsules[0].0.y;
+
+    harness.step(1);
+    assert!(harness.capsules()[0].0.y > start_y);
+
+    // Far left of the paddle, so it's never collected.
+    harness.step(60);
+    assert!(harness.capsules().is_empty());
+    assert!(harness.power_ups().0.is_empty());
+}
+
+#[test]
+fn wide_paddle_lasts_until_it_runs_out() {
+    let mut harness = Harness::new();
+    harness.start();
+    clear_level(&mut harness, &[BrickKind::Weak]);
+    let hud_is_empty =
+        |harness: &Harness| (91..96).all(|x| (0..8).all(|y| !harness.display().pixel(x, y)));
+    assert!(hud_is_empty(&harness));
+    let center = harness.paddle_position().unwrap().x + 20.0;
+
+    collect(&mut harness, PowerUp::WidePaddle);
+    assert_eq!(harness.paddle_width(), 60.0);
+    assert_eq!(harness.paddle_position().unwrap().x + 30.0, center);
+    assert!(harness.power_ups().is_active(PowerUp::WidePaddle));
+    assert!(!hud_is_empty(&harness));
+
+    harness.step(POWER_UP_FRAMES);
+    assert_eq!(harness.paddle_width(), 40.0);
+    assert!(!harness.power_ups().is_active(PowerUp::WidePaddle));
+    assert!(hud_is_empty(&harness));
+}
+
+#[test]
+fn slow_ball_slows_balls_down_for_a_while() {
+    let mut harness = Harness::new();
+    harness.start();
+    clear_level(&mut harness, &[BrickKind::Weak]);
+    let (ball, _) = harness.balls()[0];
+    // Bounces between the side walls, clear of the blocks and the paddle.
+    harness.place_ball(ball, Vec2::new(60.0, 40.0), Vec2::new(BALL_SPEED, 0.0));
+
+    collect(&mut harness, PowerUp::SlowBall);
+    assert!((harness.ball_velocity(ball).length() - BALL_SPEED * 0.6).abs() < 1e-3);
+
+    harness.step(POWER_UP_FRAMES);
+    assert!((harness.ball_velocity(ball).length() - BALL_SPEED).abs() < 1e-3);
+}
+
+#[test]
+fn multi_ball_splits_every_ball_into_three() {
+    let mut harness = Harness::new();
+    harness.start();
+    clear_level(&mut harness, &[BrickKind::Weak]);
+    let (ball, _) = harness.balls()[0];
+    harness.place_ball(ball, Vec2::new(60.0, 40.0), Vec2::new(BALL_SPEED, 0.0));
+
+    collect(&mut harness, PowerUp::MultiBall);
+    let balls = harness.balls();
+    assert_eq!(balls.len(), 3);
+    for (ball, _) in balls {
+        assert!((harness.ball_velocity(ball).length() - BALL_SPEED).abs() < 1e-3);
+    }
+    assert!(harness.power_ups().0.is_empty());
+}
+
+#[test]
+fn extra_life_adds_a_life_up_to_the_limit() {
+    let mut harness = Harness::new();
+    harness.start();
+    clear_level(&mut harness, &[BrickKind::Weak]);
+
+    collect(&mut harness, PowerUp::ExtraLife);
+    assert_eq!(harness.lives(), Some(4));
+    collect(&mut harness, PowerUp::ExtraLife);
+    assert_eq!(harness.lives(), Some(4));
+}
+
+#[test]
+fn catch_holds_the_ball_on_the_paddle_before_launching_it() {
+    let mut harness = Harness::new();
+    harness.start();
+    clear_level(&mut harness, &[BrickKind::Weak]);
+    let (ball, _) = harness.balls()[0];
+    collect(&mut harness, PowerUp::Catch);
+
+    harness.place_ball(ball, Vec2::new(60.0, 54.0), Vec2::new(0.0, 20.0));
+    harness.step(2);
+    assert_eq!(harness.ball_velocity(ball), Vec2::ZERO);
+    let held = harness.balls()[0].1;
+    assert_eq!(held.y, (HEIGHT - 5 - 4) as f32);
+
+    // The ball moves along with the paddle.
+    let paddle_x = harness.paddle_position().unwrap().x;
+    harness.script([FrameInput::RIGHT; 2]).run_script();
+    let moved = harness.paddle_position().unwrap().x - paddle_x;
+    assert!(moved > 0.0);
+    assert_near(harness.balls()[0].1, held + Vec2::new(moved, 0.0));
+
+    harness.step((CATCH_HOLD_SECS / 0.05) as usize);
+    assert!(harness.ball_velocity(ball).y < 0.0);
+}
+
+/// Lowest row of blocks, left to right.
+fn bottom_row(harness: &mut Harness) -> Vec<(bevy::prelude::Entity, Vec2, u8)> {
+    let mut blocks = harness.blocks();
+    let bottom = blocks
+        .iter()
+        .map(|(_, position, _)| position.y)
+        .fold(0.0, f32::max);
+    blocks.retain(|(_, position, _)| position.y == bottom);
+    blocks.sort_by(|(_, a, _), (_, b, _)| a.x.total_cmp(&b.x));
+    blocks
+}
+
+fn damaged_blocks(harness: &mut Harness) -> usize {
+    harness
+        .blocks()
+        .iter()
+        .filter(|(.., lives)| *lives < 2)
+        .count()
+        + (30 - harness.block_count())
+}
+
+#[test]
+fn fast_ball_does_not_tunnel_through_blocks() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_frame_time(Duration::from_secs_f64(1.0 / 120.0));
+    let (ball, _) = harness.balls()[0];
+    let (block, block_position, _) = bottom_row(&mut harness)[2];
+
+    // 25 px per tick, far more than the 3 px high block.
+    let below_block = block_position + Vec2::new(8.0, 12.0);
+    harness.place_ball(ball, below_block, Vec2::new(0.0, -3000.0));
+    harness.step(1);
+
+    let hit = harness
+        .blocks()
+        .into_iter()
+        .find(|(entity, ..)| *entity == block);
+    assert_eq!(hit.map(|(.., lives)| lives), Some(1));
+    assert_eq!(damaged_blocks(&mut harness), 1);
+    assert!(harness.ball_velocity(ball).y > 0.0);
+    assert!(harness.ball_positions()[0].y >= block_position.y + 3.0);
+}
+
+#[test]
+fn ball_between_two_blocks_hits_only_one() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_frame_time(Duration::from_secs_f64(1.0 / 120.0));
+    let (ball, _) = harness.balls()[0];
+    let row = bottom_row(&mut harness);
+    let (_, left, _) = row[1];
+    let (_, right, _) = row[2];
+    assert_eq!(right.x - left.x, 21.0);
+
+    // Straddles the gap, touching both blocks from below.
+    harness.place_ball(ball, left + Vec2::new(18.5, 3.0), Vec2::new(0.0, -600.0));
+    harness.step(1);
+
+    assert_eq!(damaged_blocks(&mut harness), 1);
+    assert!(harness.ball_velocity(ball).y > 0.0);
+}
+
+/// Frames until a ball is served again after the last one was lost.
+const RESPAWN_FRAMES: usize = (RESPAWN_SECS / 0.05) as usize + 1;
+
+/// Moves `ball` below the bottom of the screen, it's lost on the next tick.
+fn drop_ball(harness: &mut Harness, ball: Entity) {
+    harness.place_ball(ball, Vec2::new(10.0, HEIGHT as f32), Vec2::new(0.0, 20.0));
+}
+
+#[test]
+fn losing_ball_costs_life_and_respawns() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (ball, _) = harness.balls()[0];
+
+    drop_ball(&mut harness, ball);
+    harness.step(1);
+    assert_eq!(harness.lives(), Some(2));
+
+    // A short pause before the next ball is served.
+    harness.step(1);
+    assert!(harness.balls().is_empty());
+    assert_eq!(harness.state(), GameState::Playing);
+
+    harness.step(RESPAWN_FRAMES);
+    assert_eq!(harness.balls().len(), 1);
+    assert_ne!(harness.balls()[0].0, ball);
+    assert_eq!(harness.lives(), Some(2));
+}
+
+#[test]
+fn only_losing_the_last_ball_costs_a_life() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (first, _) = harness.balls()[0];
+    let second = harness.spawn_ball(Vec2::new(20.0, 40.0), Vec2::new(BALL_SPEED, 0.0));
+    let third = harness.spawn_ball(Vec2::new(100.0, 40.0), Vec2::new(-BALL_SPEED, 0.0));
+
+    drop_ball(&mut harness, first);
+    drop_ball(&mut harness, second);
+    harness.step(1);
+    assert_eq!(harness.lives(), Some(3));
+    assert_eq!(harness.balls().len(), 1);
+    assert_eq!(harness.balls()[0].0, third);
+
+    drop_ball(&mut harness, third);
+    harness.step(1);
+    assert_eq!(harness.lives(), Some(2));
+    assert!(harness.balls().is_empty());
+
+    harness.step(RESPAWN_FRAMES);
+    assert_eq!(harness.balls().len(), 1);
+}
+
+#[test]
+fn losing_all_balls_at_once_costs_one_life() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (first, _) = harness.balls()[0];
+    let second = harness.spawn_ball(Vec2::new(20.0, 40.0), Vec2::ZERO);
+
+    drop_ball(&mut harness, first);
+    drop_ball(&mut harness, second);
+    harness.step(1);
+    assert_eq!(harness.lives(), Some(2));
+}
+
+#[test]
+fn balls_bounce_off_each_other() {
+    let mut harness = Harness::new();
+    harness.start();
+    let (first, _) = harness.balls()[0];
+    harness.place_ball(first, Vec2::new(40.0, 40.0), Vec2::new(20.0, 0.0));
+    let second = harness.spawn_ball(Vec2::new(50.0, 40.0), Vec2::new(-20.0, 0.0));
+
+    let mut hit = None;
+    for _ in 0..5 {
+        harness.step(1);
+        hit = hit.or(harness
+            .collisions()
+            .into_iter()
+            .find(|collision| collision.a == first && collision.b == second));
+    }
+
+    let hit = hit.expect("balls never collided");
+    assert_eq!(hit.normal, Vec2::NEG_X);
+    // Equal masses trade their velocities head on.
+    assert_eq!(harness.ball_velocity(first), Vec2::new(-20.0, 0.0));
+    assert_eq!(harness.ball_velocity(second), Vec2::new(20.0, 0.0));
+}
+
+#[test]
+fn ball_collisions_can_be_turned_off() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness
+        .world_mut()
+        .insert_resource(BallCollisions { enabled: false });
+    let (first, _) = harness.balls()[0];
+    harness.place_ball(first, Vec2::new(40.0, 40.0), Vec2::new(20.0, 0.0));
+    let second = harness.spawn_ball(Vec2::new(50.0, 40.0), Vec2::new(-20.0, 0.0));
+
+    harness.step(5);
+    assert_eq!(harness.ball_velocity(first), Vec2::new(20.0, 0.0));
+    assert_eq!(harness.ball_velocity(second), Vec2::new(-20.0, 0.0));
+}
+
+#[test]
+fn losing_last_life_ends_game() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_lives(1);
+    let (ball, _) = harness.balls()[0];
+
+    harness.place_ball(ball, Vec2::new(10.0, HEIGHT as f32), Vec2::new(0.0, 20.0));
+    harness.step(2);
+
+    assert_eq!(harness.state(), GameState::GameOver);
+    // The round is torn down when leaving it.
+    assert_eq!(harness.lives(), None);
+    assert_eq!(harness.block_count(), 0);
+    assert!(harness.balls().is_empty());
+}
+
+#[test]
+fn losing_the_last_ball_as_the_level_is_cleared_ends_the_game() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_lives(1);
+    let (ball, _) = harness.balls()[0];
+
+    harness.place_ball(ball, Vec2::new(10.0, HEIGHT as f32), Vec2::new(0.0, 20.0));
+    harness.despawn_blocks();
+    harness.step(2);
+
+    assert_eq!(harness.state(), GameState::GameOver);
+    assert_eq!(harness.status().level, 0);
+}
+
+/// Leftmost and rightmost columns with a lit pixel.
+fn lit_columns(harness: &Harness) -> (u32, u32) {
+    let lit: Vec<u32> = (0..WIDTH)
+        .filter(|x| (0..HEIGHT).any(|y| harness.display().pixel(*x, y)))
+        .collect();
+    (lit[0], *lit.last().unwrap())
+}
+
+#[test]
+fn end_screens_fit_large_scores() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_lives(1);
+    harness.world_mut().resource_mut::<GameStatus>().score = 123_456;
+    let (ball, _) = harness.balls()[0];
+    harness.place_ball(ball, Vec2::new(10.0, HEIGHT as f32), Vec2::new(0.0, 20.0));
+    harness.step(2);
+    assert_eq!(harness.state(), GameState::GameOver);
+    let (left, right) = lit_columns(&harness);
+    assert!(left > 0 && right < WIDTH - 1, "{left}..={right}");
+
+    let mut harness = Harness::new();
+    harness.start();
+    let mut status = harness.world_mut().resource_mut::<GameStatus>();
+    status.level = LEVELS.len() - 1;
+    status.score = 123_456;
+    harness.despawn_blocks();
+    harness.step(2);
+    assert_eq!(harness.state(), GameState::CampaignCompleted);
+    let (left, right) = lit_columns(&harness);
+    assert!(left > 0 && right < WIDTH - 1, "{left}..={right}");
+}
+
+#[test]
+fn level_intro_is_shown_before_the_round() {
+    let mut harness = Harness::new();
+    harness.script([FrameInput::PRESS]).step(2);
+
+    assert_eq!(harness.state(), GameState::LevelIntro);
+    assert_eq!(harness.status().level, 0);
+    assert!(harness.balls().is_empty());
+    assert!(harness.display().lit_pixels() > 0);
+
+    // 1.5 s at 50 ms per frame
+    harness.step(30);
+    assert_eq!(harness.state(), GameState::Playing);
+    assert_eq!(harness.balls().len(), 1);
+}
+
+#[test]
+fn clearing_level_moves_on_to_next_level() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_lives(2);
+    harness.world_mut().resource_mut::<GameStatus>().score = 120;
+
+    harness.despawn_blocks();
+    // The new state takes effect at the start of the next frame.
+    harness.step(2);
+
+    assert_eq!(harness.state(), GameState::LevelIntro);
+    assert_eq!(harness.status().level, 1);
+    assert!(harness.balls().is_empty());
+
+    harness.finish_intro();
+    assert_eq!(harness.lives(), Some(2));
+    assert_eq!(harness.status().score, 120);
+    // Second layout is a diamond of 18 blocks.
+    assert_eq!(harness.block_count(), 18);
+    assert_eq!(harness.balls().len(), 1);
+}
+
+#[test]
+fn levels_set_ball_speed_and_paddle_width() {
+    assert_eq!(LEVELS[0].name, "Classic");
+    assert_eq!(LEVELS[2].ball_speed, Some(40.0));
+    assert_eq!(LEVELS[2].paddle_width, Some(32));
+
+    let mut harness = Harness::new();
+    harness.start();
+    harness.world_mut().resource_mut::<GameStatus>().level = 1;
+    harness.despawn_blocks();
+    harness.step(2).finish_intro().serve();
+    assert_eq!(harness.status().level, 2);
+
+    let (ball, _) = harness.balls()[0];
+    assert!((harness.ball_velocity(ball).length() - 40.0).abs() < 1e-3);
+    harness.script([FrameInput::RIGHT; 30]).run_script();
+    assert_eq!(harness.paddle_position().unwrap().x, WIDTH as f32 - 32.0);
+}
+
+#[test]
+fn clearing_last_level_completes_campaign() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.world_mut().resource_mut::<GameStatus>().level = LEVELS.len() - 1;
+
+    harness.despawn_blocks();
+    harness.step(2);
+
+    assert_eq!(harness.state(), GameState::CampaignCompleted);
+
+    harness.start();
+    assert_eq!(harness.status().level, 0);
+    assert_eq!(harness.status().score, 0);
+    assert_eq!(harness.block_count(), 30);
+}
+
+#[test]
+fn holding_button_serves_without_restarting_or_pausing() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.step(5);
+    let balls = harness.balls();
+
+    harness.script([FrameInput::PRESS; 3]).run_script();
+
+    assert_eq!(harness.state(), GameState::Playing);
+    assert!(harness.serve_angle().is_none());
+    assert_eq!(harness.block_count(), 30);
+    assert_eq!(
+        harness
+            .balls()
+            .iter()
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>(),
+        balls.iter().map(|(entity, _)| *entity).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn holding_the_button_pauses() {
+    let mut harness = Harness::new();
+    harness.start();
+
+    // The press serves, holding on pauses.
+    harness.script([FrameInput::PRESS; 17]).run_script().step(1);
+
+    assert!(harness.serve_angle().is_none());
+    assert_eq!(harness.state(), GameState::Paused);
+}
+
+#[test]
+fn holding_the_button_in_play_stays_paused() {
+    let mut harness = Harness::new();
+    harness.start().serve().step(1);
+
+    // The press pauses, holding on past the long press doesn't resume.
+    harness.script([FrameInput::PRESS; 20]).run_script();
+    assert_eq!(harness.state(), GameState::Paused);
+
+    harness.step(2);
+    assert_eq!(harness.state(), GameState::Paused);
+}
+
+#[test]
+fn serial_commands_play_without_a_joystick() {
+    let mut harness = Harness::with_serial();
+    harness.send("confirm\n").step(2).finish_intro();
+    let start = harness.paddle_position().unwrap();
+
+    // A move holds until the next one.
+    harness.send("move 1\n").step(2);
+    assert_near(
+        harness.paddle_position().unwrap(),
+        start + Vec2::new(10.0, 0.0),
+    );
+    harness
+        .send("move -0.5\r\n")
+        .step(1)
+        .send("move 0\n")
+        .step(3);
+    assert_near(
+        harness.paddle_position().unwrap(),
+        start + Vec2::new(7.5, 0.0),
+    );
+
+    harness.send("confirm\n").step(2);
+    assert!(harness.serve_angle().is_none());
+
+    harness.send("pause\n").step(2);
+    assert_eq!(harness.state(), GameState::Paused);
+    harness.send("back\n").step(2);
+    assert_eq!(harness.state(), GameState::Playing);
+}
+
+#[test]
+fn serial_ignores_broken_commands() {
+    assert_eq!(Command::parse("move -0.25"), Some(Command::Move(-0.25)));
+    assert_eq!(Command::parse("  confirm "), Some(Command::Confirm));
+    assert_eq!(Command::parse("move 3"), Some(Command::Move(1.0)));
+    assert_eq!(Command::parse("move"), None);
+    assert_eq!(Command::parse("move left"), None);
+    assert_eq!(Command::parse("move nan"), None);
+    assert_eq!(Command::parse("move inf"), None);
+    assert_eq!(Command::parse("pause now"), None);
+    assert_eq!(Command::parse("jump"), None);
+
+    let mut harness = Harness::with_serial();
+    harness
+        .send("jump\nconfirm please\nconfirmconfirmconfirmconfirmconfirm\n")
+        .step(3);
+    assert_eq!(harness.state(), GameState::MainMenu);
+
+    // The line after a broken one still works.
+    harness.send("confirm\n").step(2);
+    assert_eq!(harness.state(), GameState::LevelIntro);
+}
+
+/// Rotary encoder turned by `counts` during one frame.
+fn turn(counts: i32) -> FrameInput {
+    FrameInput {
+        turn: counts,
+        ..FrameInput::IDLE
+    }
+}
+
+#[test]
+fn encoder_detents_set_the_paddle_position() {
+    let mut harness = Harness::with_encoder();
+    harness.start();
+    let start = harness.paddle_position().unwrap();
+
+    harness.script([turn(8)]).run_script();
+    assert_near(
+        harness.paddle_position().unwrap(),
+        start + Vec2::new(8.0, 0.0),
+    );
+
+    // Counts short of a detent carry over to the next frame.
+    harness.script([turn(-2)]).run_script();
+    assert_near(
+        harness.paddle_position().unwrap(),
+        start + Vec2::new(8.0, 0.0),
+    );
+    harness.script([turn(-2)]).step(6);
+    assert_near(
+        harness.paddle_position().unwrap(),
+        start + Vec2::new(4.0, 0.0),
+    );
+
+    // Turning on past the edge is lost, turning back moves at once.
+    let right_edge = WIDTH as f32 - harness.paddle_width();
+    harness.script([turn(400), turn(-4)]).run_script();
+    assert_near(
+        harness.paddle_position().unwrap(),
+        Vec2::new(right_edge - 4.0, start.y),
+    );
+}
+
+#[test]
+fn encoder_sensitivity_is_configurable() {
+    let mut harness = Harness::with_encoder();
+    harness.world_mut().insert_resource(EncoderSettings {
+        counts_per_detent: 2,
+        pixels_per_detent: -3.0,
+    });
+    harness.start();
+    let start = harness.paddle_position().unwrap();
+
+    harness.script([turn(4)]).run_script();
+
+    assert_near(
+        harness.paddle_position().unwrap(),
+        start - Vec2::new(6.0, 0.0),
+    );
+}
+
+#[test]
+fn encoder_aims_and_its_switch_serves() {
+    let mut harness = Harness::with_encoder();
+    harness.start();
+    let angle = harness.serve_angle().unwrap();
+
+    harness.script([turn(-4)]).run_script();
+    let expected = (angle - 4.0 / PLAYER_SPEED * AIM_SPEED).max(-MAX_LAUNCH_ANGLE);
+    assert!((harness.serve_angle().unwrap() - expected).abs() < 1e-4);
+
+    harness.serve();
+    assert!(harness.serve_angle().is_none());
+}
+
+#[test]
+fn holding_the_encoder_switch_in_play_stays_paused() {
+    let mut harness = Harness::with_encoder();
+    harness.start().serve().step(1);
+
+    harness.script([FrameInput::PRESS; 20]).run_script();
+    assert_eq!(harness.state(), GameState::Paused);
+
+    harness.step(2);
+    assert_eq!(harness.state(), GameState::Paused);
+}
+
+/// Tilt controller rolled by `angle` radians, button up.
+fn tilted(angle: f32) -> FrameInput {
+    FrameInput {
+        tilt: angle,
+        ..FrameInput::IDLE
+    }
+}
+
+#[test]
+fn tilting_steers_the_paddle() {
+    let mut harness = Harness::with_tilt();
+    harness.world_mut().insert_resource(TiltSettings {
+        smoothing_secs: 0.0,
+        ..default()
+    });
+    harness.start();
+    let start = harness.paddle_position().unwrap();
+    let full_speed = PLAYER_SPEED * FRAME_TIME.as_secs_f32();
+
+    // Within the dead zone around level.
+    harness.script([tilted(0.05), tilted(-0.05)]).run_script();
+    assert_eq!(harness.paddle_position().unwrap(), start);
+
+    harness.script([tilted(FRAC_PI_6)]).run_script();
+    assert_near(
+        harness.paddle_position().unwrap(),
+        start + Vec2::new(full_speed, 0.0),
+    );
+
+    // Tilting further doesn't go any faster.
+    harness.script([tilted(-FRAC_PI_2)]).run_script();
+    assert_near(harness.paddle_position().unwrap(), start);
+}
+
+#[test]
+fn tilt_is_smoothed() {
+    let mut harness = Harness::with_tilt();
+    harness.world_mut().insert_resource(TiltSettings {
+        dead_zone: 0.0,
+        ..default()
+    });
+    harness.start();
+    let full_speed = PLAYER_SPEED * FRAME_TIME.as_secs_f32();
+
+    let mut steps = Vec::new();
+    for _ in 0..10 {
+        let before = harness.paddle_position().unwrap().x;
+        harness.script([tilted(FRAC_PI_6)]).run_script();
+        steps.push(harness.paddle_position().unwrap().x - before);
+    }
+
+    // A sudden tilt eases in, with a time constant of two frames.
+    let share = 1.0 - (-0.5f32).exp();
+    assert!((steps[0] - full_speed * share).abs() < 1e-3);
+    assert!(steps.windows(2).all(|pair| pair[1] > pair[0]));
+    assert!(steps[9] > 0.99 * full_speed);
+}
+
+#[test]
+fn holding_the_button_zeroes_the_tilt_without_confirming() {
+    let mut harness = Harness::with_tilt();
+    harness.world_mut().insert_resource(TiltSettings {
+        smoothing_secs: 0.0,
+        ..default()
+    });
+    // A short press serves once it's let go.
+    harness.start().serve().step(1);
+    assert!(harness.serve_angle().is_none());
+
+    // Held tilted for a long press with the ball in play, which would pause
+    // the game if it confirmed.
+    let held = FrameInput {
+        tilt: 0.4,
+        ..FrameInput::PRESS
+    };
+    harness
+        .script([held; 17])
+        .script([tilted(0.4); 3])
+        .run_script();
+    assert_eq!(harness.state(), GameState::Playing);
+    let start = harness.paddle_position().unwrap();
+    let full_speed = PLAYER_SPEED * FRAME_TIME.as_secs_f32();
+
+    // Full speed is now a full tilt away from where the button was held.
+    harness.script([tilted(0.4 - FRAC_PI_6); 4]).run_script();
+    let moved = harness.paddle_position().unwrap();
+    assert_near(moved, start - Vec2::new(4.0 * full_speed, 0.0));
+    harness.script([tilted(0.4); 5]).run_script();
+    assert_eq!(harness.paddle_position().unwrap(), moved);
+    assert_eq!(harness.state(), GameState::Playing);
+}
+
+/// Starts a round with the ball in play and presses the button to pause it.
+fn pause(harness: &mut Harness) {
+    harness.start().serve().step(1);
+    harness.script([FrameInput::PRESS]).step(2);
+    assert_eq!(harness.state(), GameState::Paused);
+}
+
+#[test]
+fn button_pauses_and_resumes_the_game() {
+    let mut harness = Harness::new();
+    harness.start().serve().step(1);
+    let hud: Vec<bool> = (0..WIDTH).map(|x| harness.display().pixel(x, 2)).collect();
+    harness.script([FrameInput::PRESS]).step(2);
+    assert_eq!(harness.state(), GameState::Paused);
+
+    // Nothing moves, not even the paddle, while the menu sits on top of the
+    // last frame.
+    let balls = harness.ball_positions();
+    let paddle = harness.paddle_position();
+    harness.script([FrameInput::LEFT; 10]).run_script();
+    assert_eq!(harness.ball_positions(), balls);
+    assert_eq!(harness.paddle_position(), paddle);
+    let paused_hud: Vec<bool> = (0..WIDTH).map(|x| harness.display().pixel(x, 2)).collect();
+    assert_eq!(paused_hud, hud);
+    // Left edge of the menu box, blocks inside the box are covered up.
+    assert!(harness.display().pixel(34, 20));
+    assert!(!harness.display().pixel(36, 20));
+
+    // Resume is picked by default.
+    harness.script([FrameInput::PRESS]).step(2);
+    assert_eq!(harness.state(), GameState::Playing);
+    harness.step(1);
+    assert_ne!(harness.ball_positions(), balls);
+    assert_eq!(harness.lives(), Some(3));
+}
+
+#[test]
+fn pause_menu_restarts_the_campaign() {
+    let mut harness = Harness::new();
+    harness.world_mut().resource_mut::<GameStatus>().level = 1;
+    pause(&mut harness);
+    harness.set_lives(1);
+    harness.world_mut().resource_mut::<GameStatus>().score = 50;
+
+    // Holding the stick moves one entry at a time.
+    harness.script([FrameInput::RIGHT; 5]).run_script();
+    harness.script([FrameInput::PRESS]).step(2);
+    assert_eq!(harness.state(), GameState::LevelIntro);
+
+    harness.finish_intro();
+    assert_eq!(harness.status().level, 0);
+    assert_eq!(harness.status().score, 0);
+    assert_eq!(harness.lives(), Some(3));
+    assert_eq!(harness.block_count(), 30);
+    assert_eq!(harness.balls().len(), 1);
+}
+
+#[test]
+fn pause_menu_quits_to_title_without_starting_a_new_game() {
+    let mut harness = Harness::new();
+    pause(&mut harness);
+
+    harness
+        .script([FrameInput::RIGHT, FrameInput::IDLE, FrameInput::RIGHT])
+        .run_script();
+    // The button stays down after picking Quit.
+    harness.script([FrameInput::PRESS; 10]).run_script();
+
+    assert_eq!(harness.state(), GameState::MainMenu);
+    assert_eq!(harness.lives(), None);
+    assert_eq!(harness.block_count(), 0);
+    assert!(harness.balls().is_empty());
+
+    harness.step(1).start();
+    assert_eq!(harness.state(), GameState::Playing);
+    assert_eq!(harness.lives(), Some(3));
+}
+
+#[test]
+fn button_restarts_after_game_over() {
+    let mut harness = Harness::new();
+    harness.start();
+    harness.set_lives(1);
+    let (ball, _) = harness.balls()[0];
+    harness.place_ball(ball, Vec2::new(10.0, HEIGHT as f32), Vec2::new(0.0, 20.0));
+    harness.step(2);
+    assert_eq!(harness.state(), GameState::GameOver);
+
+    harness.start();
+
+    assert_eq!(harness.state(), GameState::Playing);
+    assert_eq!(harness.lives(), Some(3));
+    assert_eq!(harness.block_count(), 30);
+    assert_eq!(harness.balls().len(), 1);
+}
+
+#[test]
+fn same_seed_replays_identically() {
+    let run = |seed| {
+        let mut harness = Harness::with_seed(seed);
+        harness.start();
+        harness
+            .script([FrameInput::LEFT; 10])
+            .script([FrameInput::RIGHT; 20])
+            .script([FrameInput::IDLE; 100])
+            .run_script();
+        (
+            harness.ball_positions(),
+            harness.block_count(),
+            harness.status().score,
+        )
+    };
+
+    assert_eq!(run(7), run(7));
+}
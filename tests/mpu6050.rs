@@ -0,0 +1,84 @@
+//! Tests the MPU6050 driver against a mock I2C bus that replays register reads
+//! recorded from a real sensor.
+//!
+//! Run them with `cargo host-test`.
+
+use std::f32::consts::FRAC_PI_2;
+
+use embedded_hal::i2c::ErrorKind;
+use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+use esp32_breakout_bevy::game::hal::TiltSensor;
+use esp32_breakout_bevy::game::tilt::{Mpu6050, Mpu6050Error, MPU6050_ADDRESS};
+
+/// `ACCEL_XOUT_H` to `ACCEL_ZOUT_L` with the sensor lying flat.
+const FLAT: [u8; 6] = [0x00, 0xb4, 0xff, 0x5c, 0x40, 0x28];
+/// Rolled about 30 degrees with the y axis pointing down.
+const ROLLED_RIGHT: [u8; 6] = [0x00, 0x9c, 0x20, 0x14, 0x37, 0x6c];
+/// Standing on its edge, the y axis pointing up.
+const ON_EDGE: [u8; 6] = [0xff, 0xe0, 0xbf, 0xc8, 0x00, 0x10];
+
+fn read_acceleration(bytes: [u8; 6]) -> Transaction {
+    Transaction::write_read(MPU6050_ADDRESS, vec![0x3b], bytes.to_vec())
+}
+
+#[test]
+fn init_checks_the_id_and_wakes_the_sensor() {
+    let expectations = [
+        Transaction::write_read(MPU6050_ADDRESS, vec![0x75], vec![0x68]),
+        Transaction::write(MPU6050_ADDRESS, vec![0x6b, 0x01]),
+        Transaction::write(MPU6050_ADDRESS, vec![0x1a, 0x03]),
+        Transaction::write(MPU6050_ADDRESS, vec![0x1c, 0x00]),
+    ];
+    let mut sensor = Mpu6050::new(Mock::new(&expectations), MPU6050_ADDRESS);
+
+    assert_eq!(sensor.init(), Ok(()));
+
+    sensor.release().done();
+}
+
+#[test]
+fn init_refuses_another_device() {
+    // An MPU6500 answers on the same address.
+    let expectations = [Transaction::write_read(
+        MPU6050_ADDRESS,
+        vec![0x75],
+        vec![0x70],
+    )];
+    let mut sensor = Mpu6050::new(Mock::new(&expectations), MPU6050_ADDRESS);
+
+    assert_eq!(sensor.init(), Err(Mpu6050Error::WrongDevice(0x70)));
+
+    sensor.release().done();
+}
+
+#[test]
+fn roll_comes_from_the_recorded_readings() {
+    let expectations = [
+        read_acceleration(FLAT),
+        read_acceleration(ROLLED_RIGHT),
+        read_acceleration(ON_EDGE),
+    ];
+    let mut sensor = Mpu6050::new(Mock::new(&expectations), MPU6050_ADDRESS);
+
+    assert_eq!(sensor.acceleration(), Ok([180, -164, 16424]));
+    let rolled = sensor.roll().unwrap();
+    assert!((rolled - 30f32.to_radians()).abs() < 0.01, "{rolled}");
+    let on_edge = sensor.roll().unwrap();
+    assert!((on_edge + FRAC_PI_2).abs() < 0.01, "{on_edge}");
+
+    sensor.release().done();
+}
+
+#[test]
+fn bus_errors_skip_the_reading() {
+    let expectations = [
+        read_acceleration(FLAT).with_error(ErrorKind::Other),
+        read_acceleration(FLAT),
+    ];
+    let mut sensor = Mpu6050::new(Mock::new(&expectations), MPU6050_ADDRESS);
+
+    assert_eq!(sensor.read_roll(), None);
+    assert!(sensor.read_roll().unwrap().abs() < 0.02);
+
+    sensor.release().done();
+}